@@ -0,0 +1,141 @@
+// This example demonstrates a cell that keeps the last few dependents built
+// over the same immutable owner, with undo and redo between them. This is
+// useful for editor style tooling that iteratively refines analyses of a fixed
+// snapshot.
+
+use std::collections::VecDeque;
+
+use self_cell::self_cell;
+
+#[derive(Debug)]
+struct Analysis<'a> {
+    identifiers: Vec<&'a str>,
+}
+
+#[derive(Debug)]
+struct History<'a> {
+    // Oldest first, entries after current can be restored with redo.
+    entries: VecDeque<Analysis<'a>>,
+    current: usize,
+}
+
+const MAX_HISTORY: usize = 3;
+
+impl<'a> History<'a> {
+    fn new(first: Analysis<'a>) -> Self {
+        let mut entries = VecDeque::new();
+        entries.push_back(first);
+
+        Self {
+            entries,
+            current: 0,
+        }
+    }
+
+    fn push(&mut self, analysis: Analysis<'a>) {
+        // A new entry discards everything that could have been redone.
+        self.entries.truncate(self.current + 1);
+        self.entries.push_back(analysis);
+
+        if self.entries.len() > MAX_HISTORY {
+            self.entries.pop_front();
+        }
+
+        self.current = self.entries.len() - 1;
+    }
+}
+
+self_cell!(
+    struct Snapshot {
+        owner: String,
+
+        #[covariant]
+        dependent: History,
+    }
+
+    impl {Debug}
+);
+
+impl Snapshot {
+    fn analyze(source: String) -> Self {
+        Self::new(source, |source| {
+            History::new(Analysis {
+                identifiers: source.split_whitespace().collect(),
+            })
+        })
+    }
+
+    fn refine(&mut self, refine: impl for<'a> FnOnce(&'a String, &Analysis<'a>) -> Analysis<'a>) {
+        self.with_dependent_mut(|source, history| {
+            let refined = refine(source, &history.entries[history.current]);
+            history.push(refined);
+        });
+    }
+
+    fn undo(&mut self) -> bool {
+        self.with_dependent_mut(|_, history| {
+            if history.current == 0 {
+                return false;
+            }
+
+            history.current -= 1;
+            true
+        })
+    }
+
+    fn redo(&mut self) -> bool {
+        self.with_dependent_mut(|_, history| {
+            if history.current + 1 == history.entries.len() {
+                return false;
+            }
+
+            history.current += 1;
+            true
+        })
+    }
+
+    fn current(&self) -> &Analysis<'_> {
+        let history = self.borrow_dependent();
+        &history.entries[history.current]
+    }
+}
+
+fn main() {
+    let mut snapshot = Snapshot::analyze("let x = y + 2 * z ;".into());
+    println!("snapshot.current() -> {:?}", snapshot.current());
+
+    // Each refinement only looks at the previous analysis.
+    snapshot.refine(|_, previous| Analysis {
+        identifiers: previous
+            .identifiers
+            .iter()
+            .copied()
+            .filter(|word| word.chars().all(char::is_alphanumeric))
+            .collect(),
+    });
+    snapshot.refine(|_, previous| Analysis {
+        identifiers: previous
+            .identifiers
+            .iter()
+            .copied()
+            .filter(|word| word.chars().all(char::is_alphabetic) && *word != "let")
+            .collect(),
+    });
+    println!("snapshot.current() -> {:?}", snapshot.current());
+
+    println!("snapshot.undo() -> {}", snapshot.undo());
+    println!("snapshot.current() -> {:?}", snapshot.current());
+
+    println!("snapshot.redo() -> {}", snapshot.redo());
+    println!("snapshot.redo() -> {}", snapshot.redo());
+    println!("snapshot.current() -> {:?}", snapshot.current());
+
+    // The oldest analysis is dropped once more than MAX_HISTORY are kept.
+    snapshot.refine(|source, _| Analysis {
+        identifiers: vec![&source[4..5]],
+    });
+    println!("snapshot.undo() -> {}", snapshot.undo());
+    println!("snapshot.undo() -> {}", snapshot.undo());
+    println!("snapshot.undo() -> {}", snapshot.undo());
+    println!("snapshot.current() -> {:?}", snapshot.current());
+}
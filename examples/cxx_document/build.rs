@@ -0,0 +1,10 @@
+fn main() {
+    cxx_build::bridge("src/main.rs")
+        .file("src/document.cc")
+        .flag_if_supported("-std=c++14")
+        .compile("cxx_document");
+
+    println!("cargo:rerun-if-changed=src/main.rs");
+    println!("cargo:rerun-if-changed=src/document.cc");
+    println!("cargo:rerun-if-changed=include/document.h");
+}
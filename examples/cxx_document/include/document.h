@@ -0,0 +1,4 @@
+#pragma once
+#include "rust/cxx.h"
+
+void print_document(rust::Str text);
@@ -0,0 +1,14 @@
+#include "cxx_document/include/document.h"
+#include "cxx_document/src/main.rs.h"
+
+#include <iostream>
+
+void print_document(rust::Str text) {
+  // The cell is owned by C++ and dropped at the end of this scope.
+  rust::Box<DocumentCell> doc = parse_document(text);
+
+  std::cout << "title: " << std::string(doc->title()) << std::endl;
+  for (size_t i = 0; i < doc->line_count(); ++i) {
+    std::cout << "line " << i << ": " << std::string(doc->line(i)) << std::endl;
+  }
+}
@@ -0,0 +1,84 @@
+// This example demonstrates a cell used as opaque Rust type from C++ via the
+// cxx bridge. C++ constructs and owns the parsed document through a Box and
+// queries it with shim functions, without seeing the self-referential
+// internals.
+
+use self_cell::self_cell;
+
+#[derive(Debug)]
+struct Document<'a> {
+    title: &'a str,
+    lines: Vec<&'a str>,
+}
+
+impl<'a> Document<'a> {
+    fn parse(text: &'a str) -> Self {
+        let mut lines = text.lines();
+        let title = lines.next().unwrap_or("").trim_start_matches("# ");
+
+        Self {
+            title,
+            lines: lines.filter(|line| !line.is_empty()).collect(),
+        }
+    }
+}
+
+self_cell!(
+    struct DocumentCell {
+        owner: String,
+
+        #[covariant]
+        dependent: Document,
+    }
+);
+
+// Opaque Rust types have to be 'static and can't be generic, which every cell
+// without owner lifetime is. The shims take &DocumentCell, so the borrows they
+// return are tied to the cell on the C++ side as well.
+#[cxx::bridge]
+mod ffi {
+    extern "Rust" {
+        type DocumentCell;
+
+        fn parse_document(text: &str) -> Box<DocumentCell>;
+        fn title(self: &DocumentCell) -> &str;
+        fn line_count(self: &DocumentCell) -> usize;
+        fn line(self: &DocumentCell, index: usize) -> &str;
+    }
+
+    unsafe extern "C++" {
+        include!("cxx_document/include/document.h");
+
+        fn print_document(text: &str);
+    }
+}
+
+fn parse_document(text: &str) -> Box<DocumentCell> {
+    Box::new(DocumentCell::new(text.to_owned(), |text| {
+        Document::parse(text)
+    }))
+}
+
+impl DocumentCell {
+    fn title(&self) -> &str {
+        self.borrow_dependent().title
+    }
+
+    fn line_count(&self) -> usize {
+        self.borrow_dependent().lines.len()
+    }
+
+    // A panic can't unwind into C++, cxx aborts the process instead. So an out
+    // of bounds index returns an empty line, rather than indexing directly.
+    fn line(&self, index: usize) -> &str {
+        self.borrow_dependent()
+            .lines
+            .get(index)
+            .copied()
+            .unwrap_or("")
+    }
+}
+
+fn main() {
+    ffi::print_document("# Fish\ntrout\n\ncarp\n");
+}
@@ -0,0 +1,76 @@
+// This example demonstrates a cell that is constructed with only the owner,
+// whose dependent is initialized exactly once later on, when the additional
+// input needed to build it becomes available.
+
+use self_cell::self_cell;
+
+#[derive(Debug)]
+struct Highlights<'a>(Vec<&'a str>);
+
+// Option is covariant, so the dependent can still be borrowed directly.
+type MaybeHighlights<'a> = Option<Highlights<'a>>;
+
+self_cell!(
+    struct Document {
+        owner: String,
+
+        #[covariant]
+        dependent: MaybeHighlights,
+    }
+
+    impl {Debug}
+);
+
+#[derive(Debug)]
+struct AlreadyInitialized;
+
+impl Document {
+    fn load(text: String) -> Self {
+        Self::new(text, |_| None)
+    }
+
+    fn init_dependent(
+        &mut self,
+        builder: impl for<'a> FnOnce(&'a String) -> Highlights<'a>,
+    ) -> Result<(), AlreadyInitialized> {
+        self.with_dependent_mut(|owner, dependent| {
+            if dependent.is_some() {
+                return Err(AlreadyInitialized);
+            }
+
+            *dependent = Some(builder(owner));
+            Ok(())
+        })
+    }
+
+    fn dependent(&self) -> Option<&Highlights<'_>> {
+        self.borrow_dependent().as_ref()
+    }
+}
+
+fn main() {
+    let mut document = Document::load("fn main() { let answer = 42; }".into());
+    println!("document.dependent() -> {:?}", document.dependent());
+
+    // Only known after the document was loaded, eg. read from user settings.
+    let keywords = ["fn", "let"];
+
+    let result = document.init_dependent(|text| {
+        Highlights(
+            text.split(' ')
+                .filter(|word| keywords.iter().any(|keyword| keyword == word))
+                .collect(),
+        )
+    });
+    println!("document.init_dependent(..) -> {:?}", result);
+    println!("document.dependent() -> {:?}", document.dependent());
+    println!(
+        "highlight count -> {:?}",
+        document.dependent().map(|highlights| highlights.0.len())
+    );
+
+    // The dependent can only be initialized once.
+    let result = document.init_dependent(|_| Highlights(Vec::new()));
+    println!("document.init_dependent(..) -> {:?}", result);
+    println!("document.dependent() -> {:?}", document.dependent());
+}
@@ -52,7 +52,38 @@ fn process_input(input: String) {
     println!("'{}' -> {:?}", input, names);
 }
 
+// Alternatively the cell can store the result, so that the owner is kept
+// together with the error. Eg. an IDE that keeps broken source code alongside
+// its diagnostics.
+type NamesResult<'a> = Result<Names<'a>, NameParseError>;
+
+self_cell!(
+    struct NameResultCell {
+        owner: String,
+
+        #[covariant]
+        dependent: NamesResult,
+    }
+
+    impl {Debug}
+);
+
+impl NameResultCell {
+    fn dependent(&self) -> Result<&Names<'_>, &NameParseError> {
+        self.borrow_dependent().as_ref()
+    }
+}
+
+fn process_input_keep_error(input: String) {
+    let names = NameResultCell::new(input, names_from_str);
+
+    println!("'{}' -> {:?}", names.borrow_owner(), names.dependent());
+}
+
 fn main() {
     process_input("this is good".into());
     process_input("this is bad".into());
+
+    process_input_keep_error("this is good".into());
+    process_input_keep_error("this is bad".into());
 }
@@ -0,0 +1,82 @@
+// This example demonstrates a self-referential stream. The owner is a buffer of
+// length prefixed frames, the dependent is a stream that decodes the frames
+// while borrowing the buffer. The cell itself implements Stream by polling the
+// dependent.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::{self, Stream, StreamExt};
+
+use self_cell::self_cell;
+
+#[derive(Debug)]
+struct Frame {
+    tag: u8,
+    payload: String,
+}
+
+// Async blocks are not Unpin, boxing the stream makes the dependent Unpin. The
+// dependent may be moved with with_dependent_mut, so it can't be pinned in place.
+type FrameDecoder<'a> = Pin<Box<dyn Stream<Item = Frame> + 'a>>;
+
+fn frame_decoder(buffer: &[u8]) -> FrameDecoder<'_> {
+    Box::pin(stream::unfold(buffer, |rest| async move {
+        match rest {
+            [tag, len, tail @ ..] if tail.len() >= *len as usize => {
+                let (payload, rest) = tail.split_at(*len as usize);
+                let frame = Frame {
+                    tag: *tag,
+                    payload: String::from_utf8_lossy(payload).into_owned(),
+                };
+                Some((frame, rest))
+            }
+            _ => None,
+        }
+    }))
+}
+
+self_cell!(
+    struct FrameStream {
+        owner: Vec<u8>,
+
+        #[not_covariant]
+        dependent: FrameDecoder,
+    }
+);
+
+impl FrameStream {
+    fn from_buffer(buffer: Vec<u8>) -> Self {
+        Self::new(buffer, |buffer| frame_decoder(buffer))
+    }
+}
+
+impl Stream for FrameStream {
+    type Item = Frame;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Frame>> {
+        self.get_mut()
+            .with_dependent_mut(|_, decoder| decoder.as_mut().poll_next(cx))
+    }
+}
+
+fn encode(frames: &[(u8, &str)]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    for (tag, payload) in frames {
+        buffer.push(*tag);
+        buffer.push(payload.len() as u8);
+        buffer.extend_from_slice(payload.as_bytes());
+    }
+    buffer
+}
+
+fn main() {
+    let buffer = encode(&[(1, "trout"), (2, "carp"), (1, "pike")]);
+
+    // The stream can be returned from functions and moved around freely.
+    let frame_stream = FrameStream::from_buffer(buffer);
+
+    futures::executor::block_on(frame_stream.for_each(|frame| async move {
+        println!("frame tag: {} payload: {}", frame.tag, frame.payload);
+    }));
+}
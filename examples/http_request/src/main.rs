@@ -0,0 +1,88 @@
+// This example demonstrates a parsed HTTP request that borrows all its strings
+// from the received buffer, while still being a movable value.
+
+use bytes::Bytes;
+
+use self_cell::self_cell;
+
+#[derive(Debug)]
+struct Request<'a> {
+    method: &'a str,
+    path: &'a str,
+    headers: Vec<(&'a str, &'a [u8])>,
+    body: &'a [u8],
+}
+
+enum RequestError {
+    Parse(httparse::Error),
+    Incomplete,
+}
+
+fn parse_request(buf: &Bytes) -> Result<Request<'_>, RequestError> {
+    let mut headers = [httparse::EMPTY_HEADER; 16];
+    let mut request = httparse::Request::new(&mut headers);
+
+    let body_offset = match request.parse(buf).map_err(RequestError::Parse)? {
+        httparse::Status::Complete(body_offset) => body_offset,
+        httparse::Status::Partial => return Err(RequestError::Incomplete),
+    };
+
+    Ok(Request {
+        method: request.method.unwrap_or_default(),
+        path: request.path.unwrap_or_default(),
+        headers: request
+            .headers
+            .iter()
+            .map(|header| (header.name, header.value))
+            .collect(),
+        body: &buf[body_offset..],
+    })
+}
+
+self_cell!(
+    struct RequestCell {
+        owner: Bytes,
+
+        #[covariant]
+        dependent: Request,
+    }
+
+    impl {Debug}
+);
+
+impl RequestCell {
+    fn header(&self, name: &str) -> Option<&[u8]> {
+        self.borrow_dependent()
+            .headers
+            .iter()
+            .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+}
+
+fn handle(request: RequestCell) {
+    let parsed = request.borrow_dependent();
+    println!("{} {}", parsed.method, parsed.path);
+    println!(
+        "host: {}",
+        String::from_utf8_lossy(request.header("Host").unwrap_or_default())
+    );
+    println!("body: {}", String::from_utf8_lossy(parsed.body));
+}
+
+fn main() {
+    let complete = Bytes::from_static(
+        b"POST /trout HTTP/1.1\r\nHost: example.com\r\nContent-Length: 4\r\n\r\nswim",
+    );
+    let truncated = Bytes::from_static(b"GET / HTTP/1.1\r\nHost: exa");
+    let malformed = Bytes::from_static(b"GET / HTTP/1.1\r\nHo st: example.com\r\n\r\n");
+
+    for received in [complete, truncated, malformed].iter() {
+        // The cell can be moved to a handler, without copying header strings.
+        match RequestCell::try_new(received.clone(), parse_request) {
+            Ok(request) => handle(request),
+            Err(RequestError::Parse(err)) => println!("parse error: {}", err),
+            Err(RequestError::Incomplete) => println!("incomplete request"),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// This example demonstrates a cell with one owner and a map of dependents,
+// that are built on demand per key and memoized together with the owner. Eg. a
+// query engine that derives many small analyses of the same document.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use self_cell::self_cell;
+
+#[derive(Debug)]
+struct Lines<'a>(Vec<&'a str>);
+
+type LinesByKeyword<'a> = RefCell<HashMap<String, Lines<'a>>>;
+
+self_cell!(
+    struct Document {
+        owner: String,
+
+        #[not_covariant] // Because RefCell uses UnsafeCell.
+        dependent: LinesByKeyword,
+    }
+);
+
+impl Document {
+    fn parse(text: String) -> Self {
+        Self::new(text, |_| RefCell::new(HashMap::new()))
+    }
+
+    // Calling with_dependent_for again from within func panics, because the map
+    // is still borrowed.
+    fn with_dependent_for<Ret>(
+        &self,
+        key: &str,
+        builder: impl for<'a> FnOnce(&'a String) -> Lines<'a>,
+        func: impl for<'a> FnOnce(&Lines<'a>) -> Ret,
+    ) -> Ret {
+        self.with_dependent(|owner, dependents| {
+            let mut dependents = dependents.borrow_mut();
+
+            if !dependents.contains_key(key) {
+                println!("[building dependent for '{}']", key);
+                dependents.insert(key.to_string(), builder(owner));
+            }
+
+            func(&dependents[key])
+        })
+    }
+
+    fn lines_containing(&self, keyword: &str) -> usize {
+        self.with_dependent_for(
+            keyword,
+            |text| Lines(text.lines().filter(|line| line.contains(keyword)).collect()),
+            |lines| lines.0.len(),
+        )
+    }
+
+    fn cached_keys(&self) -> usize {
+        self.with_dependent(|_, dependents| dependents.borrow().len())
+    }
+}
+
+fn main() {
+    let document = Document::parse("fn a() {}\nlet x = 1;\nfn b() { let y = 2; }".into());
+
+    println!(
+        "document.lines_containing(\"fn\") -> {}",
+        document.lines_containing("fn")
+    );
+    println!(
+        "document.lines_containing(\"let\") -> {}",
+        document.lines_containing("let")
+    );
+
+    // Served from the memoized dependent.
+    println!(
+        "document.lines_containing(\"fn\") -> {}",
+        document.lines_containing("fn")
+    );
+    println!("document.cached_keys() -> {}", document.cached_keys());
+}
@@ -39,6 +39,22 @@ impl LazyAst {
             format!("{:?}", dependent.get_or_init(|| owner.into()))
         })
     }
+
+    // Fallible parsers can use get_or_try_init, an error leaves the dependent
+    // uninitialized so the next access tries again.
+    fn try_fmt_ast(&self, max_len: usize) -> Result<String, String> {
+        self.with_dependent(|owner, dependent| {
+            dependent
+                .get_or_try_init(|| {
+                    if owner.len() <= max_len {
+                        Ok(owner.into())
+                    } else {
+                        Err(format!("code longer than {} bytes", max_len))
+                    }
+                })
+                .map(|ast| format!("{:?}", ast))
+        })
+    }
 }
 
 impl Clone for LazyAst {
@@ -58,4 +74,17 @@ fn main() {
 
     // This should not parse the Ast again, but use the existing one.
     println!("lazy_ast.fmt_ast() -> {}", lazy_ast.fmt_ast());
+
+    let fallible_ast = LazyAst::new("x * y".into(), |_| OnceCell::new());
+
+    println!(
+        "fallible_ast.try_fmt_ast(3) -> {:?}",
+        fallible_ast.try_fmt_ast(3)
+    );
+    println!("fallible_ast.is_parsed() -> {}", fallible_ast.is_parsed());
+    println!(
+        "fallible_ast.try_fmt_ast(10) -> {:?}",
+        fallible_ast.try_fmt_ast(10)
+    );
+    println!("fallible_ast.is_parsed() -> {}", fallible_ast.is_parsed());
 }
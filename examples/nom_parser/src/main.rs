@@ -0,0 +1,66 @@
+// This example demonstrates how to store the borrowing output of a nom parser
+// together with its input, and how to turn a nom error that borrows the input
+// into an owned error so the input can be recovered.
+
+use nom::bytes::complete::tag;
+use nom::character::complete::{alpha1, alphanumeric1, line_ending};
+use nom::multi::separated_list1;
+use nom::sequence::separated_pair;
+use nom::{Finish, IResult};
+
+use self_cell::self_cell;
+
+type Entries<'a> = Vec<(&'a str, &'a str)>;
+
+self_cell!(
+    struct ConfigCell {
+        owner: String,
+
+        #[covariant]
+        dependent: Entries,
+    }
+
+    impl {Debug}
+);
+
+// nom errors borrow the input, they can't leave the dependent builder.
+// Instead keep the offset into the input, which stays meaningful for the owner
+// we get back.
+#[derive(Debug)]
+struct ParseError {
+    offset: usize,
+    kind: nom::error::ErrorKind,
+}
+
+fn entries(input: &str) -> IResult<&str, Entries<'_>> {
+    separated_list1(
+        line_ending,
+        separated_pair(alpha1, tag("="), alphanumeric1),
+    )(input)
+}
+
+fn parse_config(input: &str) -> Result<Entries<'_>, ParseError> {
+    let (_rest, entries) = entries(input).finish().map_err(|err| ParseError {
+        offset: input.len() - err.input.len(),
+        kind: err.code,
+    })?;
+
+    Ok(entries)
+}
+
+fn process_input(input: String) {
+    match ConfigCell::try_new_or_recover(input, |owner| parse_config(owner)) {
+        Ok(cell) => println!("{:?}", cell.borrow_dependent()),
+        Err((input, err)) => println!(
+            "error: {:?} at {:?} in {:?}",
+            err.kind,
+            &input[err.offset..],
+            input
+        ),
+    }
+}
+
+fn main() {
+    process_input("name=trout\nsize=12".into());
+    process_input("=trout".into());
+}
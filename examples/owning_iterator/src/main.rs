@@ -0,0 +1,67 @@
+// This example demonstrates how to return an iterator over a locally created
+// String. The owner is the text, the dependent is an iterator borrowing it and
+// the cell itself implements Iterator by advancing the dependent.
+
+use std::str::Lines;
+
+use self_cell::self_cell;
+
+struct Entry {
+    level: String,
+    message: String,
+}
+
+// Lines is invariant over its lifetime, with_dependent_mut is available for
+// not_covariant dependents as well.
+self_cell!(
+    struct LogEntries {
+        owner: String,
+
+        #[not_covariant]
+        dependent: Lines,
+    }
+);
+
+impl Iterator for LogEntries {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Entry> {
+        // Items can't borrow from the cell, so they are mapped to owned values
+        // before leaving the closure.
+        self.with_dependent_mut(|_, lines| {
+            lines.find_map(|line| {
+                let mut parts = line.splitn(2, ": ");
+                match (parts.next(), parts.next()) {
+                    (Some(level), Some(message)) => Some(Entry {
+                        level: level.into(),
+                        message: message.into(),
+                    }),
+                    _ => None,
+                }
+            })
+        })
+    }
+}
+
+// Without the cell, the text would be dropped at the end of the function while
+// the returned iterator still borrows it.
+fn read_log(path: &str) -> impl Iterator<Item = Entry> {
+    // Stands in for std::fs::read_to_string(path).
+    let text = format!(
+        "INFO: opened {}\nWARN: slow disk\n\nthis line is malformed\nINFO: closed {}",
+        path, path
+    );
+
+    LogEntries::new(text, |text| text.lines())
+}
+
+fn main() {
+    for entry in read_log("fish.log") {
+        println!("[{}] {}", entry.level, entry.message);
+    }
+
+    let warnings = read_log("fish.log")
+        .filter(|entry| entry.level == "WARN")
+        .count();
+    println!("warnings -> {}", warnings);
+}
@@ -0,0 +1,69 @@
+// This example demonstrates a Python extension class backed by a cell. The
+// owner is the text passed in from Python, the dependent is a parsed document
+// borrowing from it.
+
+use pyo3::prelude::*;
+use pyo3::pybacked::PyBackedStr;
+
+use self_cell::self_cell;
+
+#[derive(Debug)]
+struct Document<'a> {
+    title: &'a str,
+    lines: Vec<&'a str>,
+}
+
+impl<'a> Document<'a> {
+    fn parse(text: &'a str) -> Self {
+        let mut lines = text.lines();
+        let title = lines.next().unwrap_or("").trim_start_matches("# ");
+
+        Self {
+            title,
+            lines: lines.filter(|line| !line.is_empty()).collect(),
+        }
+    }
+}
+
+// PyBackedStr keeps the Python str alive and gives access to its contents
+// without holding the GIL, which makes it a suitable owner. Owners that can only
+// be accessed while holding the GIL, like Py<PyString>, can't hand out
+// references that outlive the dependent builder.
+self_cell!(
+    #[pyclass]
+    struct DocumentCell {
+        owner: PyBackedStr,
+
+        #[covariant]
+        dependent: Document,
+    }
+);
+
+// The generated methods are not Python methods, the exposed API goes through
+// the regular accessors. If the dependent were not Send, the class would need
+// to be declared #[pyclass(unsendable)].
+#[pymethods]
+impl DocumentCell {
+    #[new]
+    fn parse(text: PyBackedStr) -> Self {
+        Self::new(text, |text| Document::parse(text))
+    }
+
+    #[getter]
+    fn title(&self) -> &str {
+        self.borrow_dependent().title
+    }
+
+    fn line(&self, index: usize) -> Option<&str> {
+        self.borrow_dependent().lines.get(index).copied()
+    }
+
+    fn __len__(&self) -> usize {
+        self.borrow_dependent().lines.len()
+    }
+}
+
+#[pymodule]
+fn python_document(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<DocumentCell>()
+}
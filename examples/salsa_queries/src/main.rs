@@ -0,0 +1,85 @@
+// This example demonstrates cells as values of salsa queries. The source text
+// is a salsa input, and a derived query parses it into a cell that borrows from
+// the text. Changing the input invalidates the cell like any other derived
+// value, no extra wiring is needed.
+
+use std::sync::Arc;
+
+use self_cell::self_cell;
+
+#[derive(Debug)]
+struct Ast<'a> {
+    items: Vec<&'a str>,
+}
+
+// The owner shares the text with the salsa input instead of copying it.
+self_cell!(
+    struct AstCell {
+        owner: Arc<String>,
+
+        #[covariant]
+        dependent: Ast,
+    }
+
+    // Salsa compares derived values to skip re-running dependent queries when
+    // the value did not change. Comparing owners is enough, because the
+    // dependent is built deterministically from the owner.
+    impl {Debug, PartialEq, Eq}
+);
+
+#[salsa::query_group(SourceStorage)]
+trait Source: salsa::Database {
+    #[salsa::input]
+    fn source_text(&self, file: String) -> Arc<String>;
+
+    // Query values have to be Clone, Arc makes that cheap for cells.
+    fn parse(&self, file: String) -> Arc<AstCell>;
+
+    fn item_count(&self, file: String) -> usize;
+}
+
+fn parse(db: &dyn Source, file: String) -> Arc<AstCell> {
+    println!("[parsing {}]", file);
+
+    Arc::new(AstCell::new(db.source_text(file), |text| Ast {
+        items: text
+            .split(';')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect(),
+    }))
+}
+
+fn item_count(db: &dyn Source, file: String) -> usize {
+    db.parse(file).borrow_dependent().items.len()
+}
+
+#[salsa::database(SourceStorage)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {}
+
+fn main() {
+    let mut db = Database::default();
+    let file = String::from("main.src");
+
+    db.set_source_text(file.clone(), Arc::new("let a = 1; let b = 2;".into()));
+    println!("db.item_count(file) -> {}", db.item_count(file.clone()));
+
+    // Served from the memoized cell, without parsing again.
+    println!("db.item_count(file) -> {}", db.item_count(file.clone()));
+    println!(
+        "db.parse(file).borrow_dependent() -> {:?}",
+        db.parse(file.clone()).borrow_dependent()
+    );
+
+    // Invalidates the cell, the next query parses the new text.
+    db.set_source_text(
+        file.clone(),
+        Arc::new("let a = 1; let b = 2; let c = 3;".into()),
+    );
+    println!("db.item_count(file) -> {}", db.item_count(file));
+}
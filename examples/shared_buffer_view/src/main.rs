@@ -0,0 +1,77 @@
+// This example demonstrates a columnar style refcounted buffer as owner, with a
+// typed zero-copy view into a part of it as dependent. Cloning the cell shares
+// the buffer instead of copying it.
+
+use std::sync::Arc;
+
+use self_cell::self_cell;
+
+type Buffer = Arc<[i64]>;
+
+#[derive(Debug)]
+struct Column<'a> {
+    offset: usize,
+    values: &'a [i64],
+}
+
+impl<'a> Column<'a> {
+    fn new(buffer: &'a Buffer, offset: usize, len: usize) -> Self {
+        Self {
+            offset,
+            values: &buffer[offset..offset + len],
+        }
+    }
+}
+
+self_cell!(
+    struct ColumnCell {
+        owner: Buffer,
+
+        #[covariant]
+        dependent: Column,
+    }
+
+    impl {Debug}
+);
+
+impl ColumnCell {
+    fn slice(buffer: Buffer, offset: usize, len: usize) -> Self {
+        Self::new(buffer, |buffer| Column::new(buffer, offset, len))
+    }
+
+    fn values(&self) -> &[i64] {
+        self.borrow_dependent().values
+    }
+}
+
+impl Clone for ColumnCell {
+    fn clone(&self) -> Self {
+        let column = self.borrow_dependent();
+        Self::slice(
+            Arc::clone(self.borrow_owner()),
+            column.offset,
+            column.values.len(),
+        )
+    }
+}
+
+// An operator that takes ownership of its input.
+fn sum(column: ColumnCell) -> i64 {
+    column.values().iter().sum()
+}
+
+fn main() {
+    let buffer: Buffer = (1..=8).collect::<Vec<i64>>().into();
+
+    let head = ColumnCell::slice(Arc::clone(&buffer), 0, 4);
+    let tail = ColumnCell::slice(Arc::clone(&buffer), 4, 4);
+    let tail_clone = tail.clone();
+
+    println!("head: {:?}", head.values());
+    println!("tail: {:?}", tail_clone.values());
+    println!("buffer references: {}", Arc::strong_count(&buffer));
+
+    println!("sum head: {}", sum(head));
+    println!("sum tail: {}", sum(tail));
+    println!("buffer references: {}", Arc::strong_count(&buffer));
+}
@@ -0,0 +1,55 @@
+// This example demonstrates zero-copy ingestion of a document, the raw buffer
+// is the owner and the events borrowed from it are the dependent.
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use self_cell::self_cell;
+
+type Events<'a> = Vec<Event<'a>>;
+
+self_cell!(
+    struct XmlCell {
+        owner: String,
+
+        #[covariant]
+        dependent: Events,
+    }
+);
+
+impl XmlCell {
+    fn parse(document: String) -> Result<Self, quick_xml::Error> {
+        Self::try_new(document, |document| {
+            let mut reader = Reader::from_str(document);
+            reader.trim_text(true);
+
+            let mut events = Vec::new();
+            loop {
+                match reader.read_event()? {
+                    Event::Eof => break,
+                    event => events.push(event),
+                }
+            }
+
+            Ok(events)
+        })
+    }
+
+    fn element_names(&self) -> impl Iterator<Item = &[u8]> {
+        self.borrow_dependent().iter().filter_map(|event| match event {
+            Event::Start(start) | Event::Empty(start) => Some(start.name().into_inner()),
+            _ => None,
+        })
+    }
+}
+
+fn main() {
+    let document = String::from("<fish><trout size=\"12\"/><carp>old</carp></fish>");
+    let cell = XmlCell::parse(document).unwrap();
+
+    println!("events: {}", cell.borrow_dependent().len());
+
+    for name in cell.element_names() {
+        println!("element: {}", String::from_utf8_lossy(name));
+    }
+}
@@ -45,10 +45,9 @@
 //! to compile due to its use of procedural macros.
 //!
 //! This alternative is `no_std`, uses no proc-macros, some self contained
-//! unsafe and works on stable Rust, and is miri tested. With a total of less
-//! than 300 lines of implementation code, which consists mostly of type and
-//! trait implementations, this crate aims to be a good minimal solution to the
-//! problem of self-referential structs.
+//! unsafe and works on stable Rust, and is miri tested. With implementation
+//! code that consists mostly of type and trait implementations, this crate aims
+//! to be a good minimal solution to the problem of self-referential structs.
 //!
 //! It has undergone [community code
 //! review](https://users.rust-lang.org/t/experimental-safe-to-use-proc-macro-free-self-referential-structs-in-stable-rust/52775)
@@ -63,8 +62,9 @@
 //! Completed self_cell v0.7.0 in 0.2s
 //! ```
 //!
-//! Because it does **not** use proc-macros, and has 0 dependencies
-//! compile-times are fast.
+//! Because it does **not** use proc-macros, and has no required dependencies
+//! compile-times are fast. The optional "old_rust", "tracing",
+//! "portable-atomic" and "serde" features pull in dependencies.
 //!
 //! Measurements done on a slow laptop.
 //!
@@ -129,6 +129,10 @@
 //! makes it safe to move the generated SelfCell but you have to pay for the
 //! heap allocation.
 //!
+//! Only the owner value itself is moved, data it points to stays in place. For
+//! example a `Cow<'static, str>` owner holding `Cow::Borrowed` is a pointer and
+//! a length, the dependent borrows the static data directly without copying it.
+//!
 //! See the documentation for [`self_cell`] to dive further into the details.
 //!
 //! Or take a look at the advanced examples:
@@ -141,7 +145,89 @@
 //! - [How to handle dependents that take a mutable reference](https://github.com/Voultapher/self_cell/tree/main/examples/mut_ref_to_owner_in_builder) see also [`MutBorrow`]
 //!
 //! - [How to use an owner type with
-//!     lifetime](https://github.com/Voultapher/self_cell/tree/main/examples/owner_with_lifetime)
+//!   lifetime](https://github.com/Voultapher/self_cell/tree/main/examples/owner_with_lifetime)
+//!
+//! - [How to store the output of a nom
+//!   parser](https://github.com/Voultapher/self_cell/tree/main/examples/nom_parser)
+//!
+//! - [How to ingest borrowed XML events without
+//!   copying](https://github.com/Voultapher/self_cell/tree/main/examples/xml_events)
+//!
+//! - [How to parse an HTTP request without copying
+//!   headers](https://github.com/Voultapher/self_cell/tree/main/examples/http_request)
+//!
+//! - [How to share a refcounted buffer between typed
+//!   views](https://github.com/Voultapher/self_cell/tree/main/examples/shared_buffer_view)
+//!
+//! - [How to initialize the dependent once, after the
+//!   owner](https://github.com/Voultapher/self_cell/tree/main/examples/deferred_dependent)
+//!
+//! - [How to keep an undo history of
+//!   dependents](https://github.com/Voultapher/self_cell/tree/main/examples/analysis_history)
+//!
+//! - [How to memoize dependents per key over one
+//!   owner](https://github.com/Voultapher/self_cell/tree/main/examples/keyed_cache)
+//!
+//! - [How to return an iterator over a locally created
+//!   String](https://github.com/Voultapher/self_cell/tree/main/examples/owning_iterator)
+//!
+//! - [How to implement Stream for a cell with a borrowing
+//!   stream](https://github.com/Voultapher/self_cell/tree/main/examples/frame_stream)
+//!
+//! - [How to expose a cell as Python class with
+//!   PyO3](https://github.com/Voultapher/self_cell/tree/main/examples/python_document)
+//!
+//! - [How to use a cell as opaque type in a cxx
+//!   bridge](https://github.com/Voultapher/self_cell/tree/main/examples/cxx_document)
+//!
+//! - [How to use cells as derived values of salsa
+//!   queries](https://github.com/Voultapher/self_cell/tree/main/examples/salsa_queries)
+//!
+//! ### Optional features
+//!
+//! - "tracing": Emits [tracing](https://docs.rs/tracing) diagnostics. Dependent
+//!   construction runs inside a trace level `self_cell::build` span, that
+//!   records the struct name in its `cell` field. Building a new dependent for
+//!   an existing cell, eg. with `with_owner_mut` or `map_dependent`, runs
+//!   inside a `self_cell::rebuild` span, that records the struct name and the
+//!   called method in its `method` field. Dropping a cell and `into_owner` emit
+//!   trace level events.
+//!
+//! - "erased_drop": Cells share one non-generic implementation of the drop
+//!   guard and deallocation logic, only dropping the owner and dependent is
+//!   generated per cell type. This reduces binary size for programs with many
+//!   different cell types, eg. plugin hosts, at the cost of indirect calls when
+//!   dropping a cell.
+//!
+//! - "heap_profiling": Cells allocate through a never inlined function
+//!   generated for every cell type, eg. `AstCell::_alloc_joined`. Heap
+//!   profilers like [dhat](https://docs.rs/dhat) or heaptrack then attribute
+//!   the allocation to the cell type, instead of an `alloc::alloc` frame
+//!   inside macro expanded code.
+//!
+//! - "layout_dump": Generates a `layout_dump(&self)` method, that returns the
+//!   `LayoutDump` of the joined allocation. Printing it shows addresses,
+//!   sizes and offsets of owner, dependent and padding. Useful to learn how
+//!   cells are laid out and to debug wrappers built on the cell internals.
+//!
+//! - "live_cells": Generates a `live_cells()` function for every cell type,
+//!   that returns the number of cells of that type that are currently alive.
+//!   Useful to detect cells that are leaked, eg. with `mem::forget`.
+//!
+//! - "portable-atomic": Uses the atomics of
+//!   [portable-atomic](https://docs.rs/portable-atomic) instead of
+//!   `core::sync::atomic` for [`MutBorrow`] and the "live_cells" feature. This
+//!   allows using them on targets without atomic read-modify-write operations,
+//!   like thumbv6m. Depending on the target portable-atomic needs one of its
+//!   own features or cfgs enabled too, eg. "critical-section".
+//!
+//! - "serde": Enables `Serialize` and `Deserialize` as automatic derives, which
+//!   serialize the owner with [serde](https://docs.rs/serde) and rebuild the
+//!   dependent from it. `Deserialize(try_from)` builds the dependent with
+//!   `TryFrom` instead and reports its error as deserialization error.
+//!
+//! Optional features that pull in dependencies are not covered by the minimum
+//! required rustc version.
 //!
 //! ### Min required rustc version
 //!
@@ -160,6 +246,10 @@
 #[doc(hidden)]
 pub extern crate alloc;
 
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub extern crate serde;
+
 #[doc(hidden)]
 pub mod unsafe_self_cell;
 
@@ -214,6 +304,59 @@ pub mod unsafe_self_cell;
 /// ) -> Result<Self, ($Owner, Err)>
 /// ```
 ///
+/// ```ignore
+/// // Not available with the "old_rust" feature.
+/// async fn new_async<Builder>(owner: $Owner, dependent_builder: Builder) -> Self
+/// where
+///     Builder: for<'a> AsyncBuilder<&'a $Owner, Output = $Dependent<'a>>
+/// ```
+///
+/// ```ignore
+/// // Not available with the "old_rust" feature.
+/// async fn try_new_async<Builder, Err>(
+///     owner: $Owner,
+///     dependent_builder: Builder
+/// ) -> Result<Self, Err>
+/// where
+///     Builder: for<'a> AsyncBuilder<&'a $Owner, Output = Result<$Dependent<'a>, Err>>
+/// ```
+///
+/// ```ignore
+/// // Not available with the "old_rust" feature.
+/// fn try_new_or_recover_async<Builder, Err>(
+///     owner: $Owner,
+///     dependent_builder: Builder
+/// ) -> impl Future<Output = Result<Self, ($Owner, Err)>>
+/// where
+///     Builder: for<'a> AsyncBuilder<&'a $Owner, Output = Result<$Dependent<'a>, Err>>
+/// ```
+///
+/// [`AsyncBuilder`] is implemented for functions like `async fn(&Owner) ->
+/// Dependent<'_>`. Dropping the future returned by one of the async
+/// constructors before it completes, drops the builder future first, then the
+/// owner and frees the allocation. Cancelled construction leaks nothing.
+///
+/// If allocating the cell fails, all constructors call
+/// `alloc::alloc::handle_alloc_error`, like `Box::new` does. Depending on the
+/// target this aborts or runs the allocation error hook of the program. The
+/// fallible constructors only return errors of the dependent builder.
+///
+/// There is no per cell or crate-wide setting to choose between panicking,
+/// calling a hook or returning an error on these failures:
+///
+/// - Allocation failure already goes through the program-wide policy of
+///   `handle_alloc_error`, so a second policy in self_cell could only disagree
+///   with it. Returning it from the fallible constructors would need the
+///   unstable `core::alloc::AllocError` or a new error type mixed into the
+///   user's `Err`.
+/// - A panic while the dependent is dropped and not yet rebuilt, for example
+///   in the closure of `with_owner_mut`, always aborts. Neither dropping nor
+///   using the cell is sound in that state, so it can't be turned into an
+///   error or a hook that returns.
+/// - Cells have no poisoned state. The remaining panics, like calling
+///   [`MutBorrow::borrow_mut`] twice, are misuse of the API, not runtime
+///   failures.
+///
 /// The macro implements these methods:
 ///
 /// ```ignore
@@ -240,9 +383,65 @@ pub mod unsafe_self_cell;
 /// ```
 ///
 /// ```ignore
+/// fn replace_dependent_with<Ret>(
+///     &mut self,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> $Dependent<'a>,
+///     consume_old: impl for<'a> ::core::ops::FnOnce($Dependent<'a>) -> Ret
+/// ) -> Ret
+/// ```
+///
+/// ```ignore
+/// fn map_dependent(
+///     &mut self,
+///     func: impl for<'a> ::core::ops::FnOnce($Dependent<'a>) -> $Dependent<'a>
+/// )
+/// ```
+///
+/// ```ignore
+/// fn with_owner_mut<Ret>(
+///     &mut self,
+///     func: impl ::core::ops::FnOnce(&mut $Owner) -> Ret,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> $Dependent<'a>
+/// ) -> Ret
+/// ```
+///
+/// ```ignore
+/// fn replace_owner(
+///     &mut self,
+///     new_owner: $Owner,
+///     dependent_builder: impl for<'a> ::core::ops::FnOnce(&'a $Owner) -> $Dependent<'a>
+/// ) -> $Owner
+/// ```
+///
+/// ```ignore
 /// fn into_owner(self) -> $Owner
 /// ```
 ///
+/// ```ignore
+/// fn into_owner_and_map<Ret>(
+///     self,
+///     func: impl for<'a> ::core::ops::FnOnce($Dependent<'a>) -> Ret
+/// ) -> ($Owner, Ret)
+/// ```
+///
+/// ```ignore
+/// fn into_raw(self) -> *mut ()
+/// ```
+///
+/// ```ignore
+/// unsafe fn from_raw(raw: *mut ()) -> Self
+/// ```
+///
+/// With the "live_cells" feature enabled, the macro also implements:
+///
+/// ```ignore
+/// fn live_cells() -> usize
+/// ```
+///
+/// The macro implements these traits:
+///
+/// - [`DynCell`] with `type Owner = $Owner`.
+///
 ///
 /// ### Parameters:
 ///
@@ -257,10 +456,15 @@ pub mod unsafe_self_cell;
 /// - `$Owner:ty` Type of owner. This has to have a `'static` lifetime. Example:
 ///   `String`.
 ///
+///   The owner itself has to be sized, but it can be a box of an unsized type
+///   like `Box<str>`, `Box<[u8]>` or `Box<dyn Trait>`. The dependent can then
+///   borrow the pointee directly, eg. `|owner: &Box<str>| &**owner`.
+///
 /// - `$Dependent:ident` Name of the dependent type without specified lifetime.
 ///   This can't be a nested type name. As workaround either create a type alias
 ///   `type Dep<'a> = Option<Vec<&'a str>>;` or create a new-type `struct
-///   Dep<'a>(Option<Vec<&'a str>>);`. Example: `Ast`.
+///   Dep<'a>(Option<Vec<&'a str>>);`. Example: `Ast`. The elided lifetime
+///   can be written out, eg. `Ast<'_>`.
 ///
 ///   `$Covariance:ident` Marker declaring if `$Dependent` is
 ///   [covariant](https://doc.rust-lang.org/nightly/nomicon/subtyping.html).
@@ -273,7 +477,8 @@ pub mod unsafe_self_cell;
 ///     with interior mutability like `Cell`, which can lead to UB in safe code.
 ///     Which would violate the promise of this library that it is safe-to-use.
 ///     If you accidentally mark a type that is not covariant as covariant, you
-///     will get a compile time error.
+///     will get a compile time error. [`assert_covariance`] performs the same
+///     check on its own.
 ///
 ///   * **not_covariant**: This generates no additional code but you can use the
 ///     `with_dependent` function. See [How to build a lazy AST with
@@ -284,8 +489,29 @@ pub mod unsafe_self_cell;
 ///   dependent value. This is safe to do because notionally you are replacing
 ///   pointers to a value not the other way around.
 ///
-/// - `impl {$($AutomaticDerive:ident),*},` Optional comma separated list of
-///   optional automatic trait implementations. Possible Values:
+///   There is deliberately no accessor returning `(&$Owner, &mut
+///   $Dependent<'_>)`, not even for covariant dependents. With `&'a mut
+///   $Dependent<'a>` you could store references that only live for `'a`
+///   inside the dependent, which would dangle once the borrow ends. The
+///   closure passed to `with_dependent_mut` is generic over the dependent
+///   lifetime, which rules this out. Early returns and `?` still work inside
+///   the closure by returning a `Result` from it:
+///
+///   ```ignore
+///   cell.with_dependent_mut(|owner, ast| -> Result<(), ParseError> {
+///       ast.spans.push(resolve_span(owner, new_span)?);
+///       Ok(())
+///   })?;
+///   ```
+///
+/// - `$(#[$ImplMeta:meta])*` Optional meta items placed after the struct
+///   definition, that are applied to the generated inherent impl block. Eg.
+///   `#[allow(clippy::needless_lifetimes)]`. The constructor `new`, `into_raw`
+///   and the accessors returning references are marked `#[must_use]`.
+///
+/// - `impl {$($AutomaticDerive:ident $(($($DeriveArg:tt)*))?),*},` Optional
+///   comma separated list of optional automatic trait implementations, some
+///   take an argument that selects their behavior. Possible Values:
 ///
 ///   * **Debug**: Prints the debug representation of owner and dependent.
 ///     Example: `AstCell { owner: "fox = cat + dog", dependent: Ast(["fox",
@@ -295,14 +521,126 @@ pub mod unsafe_self_cell;
 ///     this assumes that `Dependent<'a>::From<&'a Owner>` is deterministic, so
 ///     that only comparing owner is enough.
 ///
-///   * **Eq**: Will implement the trait marker `Eq` for `$StructName`. Beware
-///     if you select this `Eq` will be implemented regardless if `$Owner`
-///     implements `Eq`, that's an unfortunate technical limitation.
+///   * **PartialEq(dependent)**: Logic `*self.borrow_dependent() ==
+///     *other.borrow_dependent()`, for dependents that are not determined by
+///     the owner alone. Only available if dependent is covariant.
+///     `PartialEq(owner)` is the same as `PartialEq`.
+///
+///   * **PartialEq(Target)**: Implements `PartialEq<Target>` with logic
+///     `*self.borrow_owner() == *other`, for any other `Target` type the owner
+///     can be compared with, eg. `PartialEq(str)` for a `String` owner allows
+///     `cell == *"abc"` and `PartialEq(&str)` allows `cell == "abc"`.
+///
+///   * **Eq**: Will implement the trait marker `Eq` for `$StructName`. Fails to
+///     compile if `$Owner` doesn't implement `Eq`.
+///     `Eq(dependent)` goes with `PartialEq(dependent)` and requires the
+///     dependent to implement `Eq` instead. Requesting `Eq` for one side and
+///     `PartialEq` for the other is a compile error. `Eq(owner)` is the same
+///     as `Eq`.
 ///
 ///   * **Hash**: Logic `self.borrow_owner().hash(state);`, this assumes that
 ///     `Dependent<'a>::From<&'a Owner>` is deterministic, so that only hashing
 ///     owner is enough.
 ///
+///   * **Hash(dependent)**: Hashes the dependent with `with_dependent`, to go
+///     with `PartialEq(dependent)`. `Hash(owner)` is the same as `Hash`.
+///     Requesting `Hash` together with `PartialEq(dependent)` is a compile
+///     error, because equal cells have to hash the same.
+///
+///   * **PartialOrd**: Logic
+///     `self.borrow_owner().partial_cmp(other.borrow_owner())`, same
+///     assumption as `PartialEq`.
+///
+///   * **Ord**: Logic `self.borrow_owner().cmp(other.borrow_owner())`. Requires
+///     `PartialEq`, `Eq` and `PartialOrd` to be implemented as well, eg. with
+///     `impl {PartialEq, Eq, PartialOrd, Ord}`. `PartialOrd` and `Ord` are not
+///     available with `PartialEq(dependent)`, because they only order by the
+///     owner.
+///
+///   * **Default**: Logic `Self::new(Owner::default(), |owner| owner.into())`,
+///     `Dependent<'a>::From<&'a Owner>` has to be implemented.
+///
+///   * **AsRef**: Implements `AsRef<$Owner>` with `borrow_owner`.
+///     `AsRef(Target)` implements `AsRef<Target>` instead, for a `Target` the
+///     owner derefs to, eg. `AsRef(str)` for a `String` owner.
+///
+///   * **Borrow**: Implements `Borrow<$Owner>` with `borrow_owner`, like with
+///     `AsRef` there is `Borrow(Target)` as well. `Borrow` requires that
+///     `PartialEq`, `Eq` and `Hash` behave the same for the cell and the
+///     borrowed value, which holds for the owner based derives. Requesting
+///     `Borrow` together with `PartialEq(dependent)` or `Hash(dependent)` is a
+///     compile error, `AsRef` has no such requirement.
+///
+///   * **Display**: Forwards `Display` to the dependent. `Display(owner)`
+///     forwards to the owner instead, `Display(dependent)` is the same as
+///     `Display`.
+///
+///   * **Error**: Implements `std::error::Error`, forwarding `source` to the
+///     dependent. This allows returning an error that borrows from its input,
+///     eg. a parse error with spans into the source text, as `Box<dyn Error>`.
+///     Requires `Debug` and `Display` to be implemented as well, eg. with
+///     `impl {Debug, Display, Error}`. Requires `std`.
+///
+///   * **Read**: Forwards `std::io::Read` to the dependent, which has to
+///     implement `Read` itself, eg. `Cursor<&'a [u8]>` over a `Vec<u8>` owner.
+///     Requires `std`.
+///
+///   * **BufRead**: Forwards `std::io::BufRead` to the dependent. Requires
+///     `Read` to be implemented as well, eg. with `impl {Read, BufRead}`.
+///
+///   * **Write**: Forwards `std::io::Write` to the dependent, eg.
+///     `Cursor<&'a mut Vec<u8>>` over a [`MutBorrow`] owner. Requires `std`.
+///
+///   * **Serialize**: Logic `self.borrow_owner().serialize(serializer)`, the
+///     dependent is not serialized. Requires the "serde" feature.
+///
+///   * **Deserialize**: Deserializes the owner and builds the dependent with
+///     `Dependent<'a>::From<&'a Owner>`, which has to be implemented. Requires
+///     the "serde" feature.
+///
+///   * **Deserialize(try_from)**: Same as `Deserialize` for cells whose
+///     dependent implements `TryFrom<&'a Owner>` instead. If building the
+///     dependent fails, the error is reported with `serde::de::Error::custom`,
+///     so the error type has to implement `Display`. `Deserialize(from)` is
+///     the same as `Deserialize`.
+///
+///   * **Deserialize(migrate = path)**: Same as `Deserialize`, but calls the
+///     function `path(owner: $Owner) -> $Owner` with the deserialized owner
+///     before the dependent is built. This allows upgrading persisted owners
+///     from older formats, eg. config text with renamed keys.
+///     `Deserialize(try_from, migrate = path)` does the same for
+///     `Deserialize(try_from)`, which can reject owners that can't be
+///     migrated.
+///
+///   * **Serialize(module)**, **Deserialize(module)**: Same as `Serialize` and
+///     `Deserialize`, but the owner is serialized with `module::serialize` and
+///     deserialized with `module::deserialize`, like `#[serde(with =
+///     "module")]` does for fields. Eg. to serialize a `Vec<u8>` owner as
+///     base64 string. `module` can be a path like `crate::base64`.
+///
+///   * **Clone(from)**: Logic `Self::new(self.borrow_owner().clone(), |owner|
+///     owner.into())`, `Dependent<'a>::From<&'a Owner>` has to be implemented.
+///     `clone_from` is the default one, that assigns a new clone. Reusing the
+///     allocation would drop the dependent before the new one is built, and
+///     a panic in between could only abort.
+///
+///   * **Clone(try_from)**: Same as `Clone(from)` for cells whose dependent
+///     implements `TryFrom<&'a Owner>` instead. Panics if rebuilding the
+///     dependent fails, which can't happen for deterministic builders.
+///
+///   * **Clone(builder = path)**: Logic `Self::new(self.borrow_owner().clone(),
+///     |owner| path(owner))`, for cells that are all built by the same
+///     function `path`, eg. `fn parse<'a>(source: &'a str) -> Ast<'a>`.
+///
+///   Plain `Clone` is not available as `AutomaticDerive`, because the cell
+///   doesn't store the dependent builder needed to rebuild the dependent for
+///   the clone. Requesting it produces a compile error that explains how to
+///   implement it by hand. `Clone(from)` uses `From` as builder instead, and
+///   `Clone(builder = path)` a builder function chosen per cell type. Storing
+///   the builder in each cell is not supported, the cell is a single pointer
+///   to the heap allocation holding owner and dependent, which `into_owner`
+///   and `into_raw` rely on.
+///
 ///   All `AutomaticDerive` are optional and you can implement you own version
 ///   of these traits. The declared struct is part of your module and you are
 ///   free to implement any trait in any way you want. Access to the unsafe
@@ -316,6 +654,224 @@ pub mod unsafe_self_cell;
 ///   limitations, no `AutomaticDerive` are supported if an owner lifetime is
 ///   provided.
 ///
+/// ### Clearable dependents
+///
+/// A dependent wrapped in `Option` can be dropped and rebuilt while the owner
+/// stays alive, eg. to evict expensive analyses under memory pressure while
+/// retaining the source. `Option` is covariant, so the dependent can still be
+/// borrowed directly:
+///
+/// ```rust
+/// use self_cell::self_cell;
+///
+/// type Words<'a> = Vec<&'a str>;
+/// type MaybeWords<'a> = Option<Words<'a>>;
+///
+/// self_cell!(
+///     struct WordsCache {
+///         owner: String,
+///
+///         #[covariant]
+///         dependent: MaybeWords,
+///     }
+/// );
+///
+/// impl WordsCache {
+///     fn clear_dependent(&mut self) {
+///         self.with_dependent_mut(|_, dependent| *dependent = None);
+///     }
+///
+///     fn set_dependent(&mut self, builder: impl for<'a> FnOnce(&'a String) -> Words<'a>) {
+///         self.with_dependent_mut(|owner, dependent| *dependent = Some(builder(owner)));
+///     }
+///
+///     fn dependent(&self) -> Option<&Words<'_>> {
+///         self.borrow_dependent().as_ref()
+///     }
+/// }
+///
+/// let mut cache = WordsCache::new("a b c".into(), |owner| Some(owner.split(' ').collect()));
+/// assert_eq!(cache.dependent().map(|words| words.len()), Some(3));
+///
+/// cache.clear_dependent();
+/// assert_eq!(cache.dependent(), None);
+///
+/// cache.set_dependent(|owner| owner.split(' ').take(1).collect());
+/// assert_eq!(cache.dependent(), Some(&vec!["a"]));
+/// ```
+///
+/// See also the [deferred dependent
+/// example](https://github.com/Voultapher/self_cell/tree/main/examples/deferred_dependent)
+/// for a dependent that is initialized once, after the owner.
+///
+/// ### Projections into a shared cell
+///
+/// A cell can be the owner of another cell. With a refcounted parent cell as
+/// owner, any number of cells can project into parts of the parent's dependent,
+/// eg. one function of a big shared AST, without copying it:
+///
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use self_cell::self_cell;
+///
+/// type Tokens<'a> = Vec<&'a str>;
+///
+/// self_cell!(
+///     struct TokensCell {
+///         owner: String,
+///
+///         #[covariant]
+///         dependent: Tokens,
+///     }
+/// );
+///
+/// type TokensView<'a> = &'a [&'a str];
+///
+/// self_cell!(
+///     struct TokensViewCell {
+///         owner: Arc<TokensCell>,
+///
+///         #[covariant]
+///         dependent: TokensView,
+///     }
+/// );
+///
+/// let parent = Arc::new(TokensCell::new("fn a ( ) fn b ( )".into(), |owner| {
+///     owner.split(' ').collect()
+/// }));
+///
+/// let second_fn = TokensViewCell::new(parent.clone(), |parent| &parent.borrow_dependent()[4..]);
+/// drop(parent);
+///
+/// assert_eq!(second_fn.borrow_dependent(), &["fn", "b", "(", ")"]);
+/// ```
+///
+/// ### Custom teardown
+///
+/// The macro implements `Drop` for the cell, which drops the dependent first and
+/// then the owner. To run custom logic at either step, eg. return the owner's
+/// buffer to a pool, wrap the type in a newtype that implements `Drop`. This
+/// works for foreign types too:
+///
+/// ```rust
+/// use std::cell::RefCell;
+///
+/// use self_cell::self_cell;
+///
+/// thread_local! {
+///     static POOL: RefCell<Vec<Vec<u8>>> = RefCell::new(Vec::new());
+/// }
+///
+/// struct PooledBuffer(Vec<u8>);
+///
+/// impl Drop for PooledBuffer {
+///     fn drop(&mut self) {
+///         let mut buffer = std::mem::take(&mut self.0);
+///         buffer.clear();
+///         POOL.with(|pool| pool.borrow_mut().push(buffer));
+///     }
+/// }
+///
+/// type Lines<'a> = Vec<&'a [u8]>;
+///
+/// self_cell!(
+///     struct LinesCell {
+///         owner: PooledBuffer,
+///
+///         #[covariant]
+///         dependent: Lines,
+///     }
+/// );
+///
+/// let cell = LinesCell::new(PooledBuffer(b"a\nb".to_vec()), |owner| {
+///     owner.0.split(|byte| *byte == b'\n').collect()
+/// });
+/// assert_eq!(cell.borrow_dependent().len(), 2);
+///
+/// drop(cell);
+/// assert_eq!(POOL.with(|pool| pool.borrow().len()), 1);
+/// ```
+///
+/// `into_owner` returns the newtype, its `Drop` runs once the caller is done
+/// with it.
+///
+/// ### Looking up cells by owner
+///
+/// With `impl {PartialEq, Eq, Hash}` cells compare and hash like their owner.
+/// Implementing `Borrow` for the borrowed form of the owner then allows
+/// probing a `HashSet` or `HashMap` of cells with a plain key, without
+/// constructing a throwaway cell:
+///
+/// ```rust
+/// use std::collections::HashSet;
+///
+/// use self_cell::self_cell;
+///
+/// type Words<'a> = Vec<&'a str>;
+///
+/// self_cell!(
+///     struct WordsCell {
+///         owner: String,
+///
+///         #[covariant]
+///         dependent: Words,
+///     }
+///
+///     // String and str hash and compare the same, as Borrow requires.
+///     impl {PartialEq, Eq, Hash, Borrow(str)}
+/// );
+///
+/// let mut cells = HashSet::new();
+/// cells.insert(WordsCell::new("a b c".into(), |owner| owner.split(' ').collect()));
+///
+/// let cell = cells.get("a b c").unwrap();
+/// assert_eq!(cell.borrow_dependent().len(), 3);
+/// assert!(!cells.contains("a b"));
+/// ```
+///
+/// Maps that support custom key comparison, like hashbrown with its
+/// `Equivalent` trait, work the same way. hashbrown implements `Equivalent`
+/// for every `Borrow` implementation, an explicit `impl Equivalent<WordsCell>
+/// for str` is only needed if the cell should not implement `Borrow`.
+///
+/// ### Sealed cells
+///
+/// Code in the same module as the macro invocation can access the private
+/// `unsafe_self_cell` field and construct the struct literally. Wrapping the
+/// declaration in `mod $ModName { ... }` generates the cell inside a private
+/// module of that name and only re-exports the struct, so that the internals
+/// are out of reach even for the surrounding module:
+///
+/// ```rust
+/// use self_cell::self_cell;
+///
+/// type Words<'a> = Vec<&'a str>;
+///
+/// self_cell!(
+///     mod words_cell {
+///         pub struct WordsCell {
+///             owner: String,
+///
+///             #[covariant]
+///             dependent: Words,
+///         }
+///
+///         impl {Debug}
+///     }
+/// );
+///
+/// fn main() {
+///     let cell = WordsCell::new("a b".into(), |owner| owner.split(' ').collect());
+///     assert_eq!(cell.borrow_dependent().len(), 2);
+/// }
+/// ```
+///
+/// The sealed module glob imports the surrounding module, so `$Owner` and
+/// `$Dependent` are resolved as usual, unless they are `super::` relative paths
+/// or types local to a function body. Supported visibilities are private,
+/// `pub(crate)` and `pub`.
+///
 #[macro_export]
 macro_rules! self_cell {
 (
@@ -324,10 +880,11 @@ macro_rules! self_cell {
         owner: $Owner:ty,
 
         #[$Covariance:ident]
-        dependent: $Dependent:ident,
+        dependent: $Dependent:ident $(<'_>)?,
     }
 
-    $(impl {$($AutomaticDerive:ident),*})?
+    $(#[$ImplMeta:meta])*
+    $(impl {$($AutomaticDerive:ident $(($($DeriveArg:tt)*))?),*})?
 ) => {
     #[repr(transparent)]
     $(#[$StructMeta])*
@@ -341,6 +898,7 @@ macro_rules! self_cell {
         $(owner_marker: $crate::_covariant_owner_marker!($Covariance, $OwnerLifetime) ,)?
     }
 
+    $(#[$ImplMeta])*
     impl $(<$OwnerLifetime>)? $StructName $(<$OwnerLifetime>)? {
         /// Constructs a new self-referential struct.
         ///
@@ -348,6 +906,7 @@ macro_rules! self_cell {
         /// Followed by construction of the dependent value, by calling
         /// `dependent_builder` with a shared reference to the owner that
         /// remains valid for the lifetime of the constructed struct.
+        #[must_use]
         $Vis fn new(
             owner: $Owner,
             dependent_builder: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> $Dependent<'_q>
@@ -373,7 +932,7 @@ macro_rules! self_cell {
                 let layout = $crate::alloc::alloc::Layout::new::<JoinedCell>();
                 assert!(layout.size() != 0);
 
-                let joined_void_ptr = NonNull::new($crate::alloc::alloc::alloc(layout)).unwrap();
+                let joined_void_ptr = $crate::unsafe_self_cell::check_alloc($crate::_heap_profiling!(alloc layout), layout);
 
                 let mut joined_ptr = joined_void_ptr.cast::<JoinedCell>();
 
@@ -386,9 +945,13 @@ macro_rules! self_cell {
                 let drop_guard =
                     $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr);
 
+                let build_span = $crate::unsafe_self_cell::BuildSpan::enter(stringify!($StructName));
+
                 // Initialize dependent with owner reference in final place.
                 dependent_ptr.write(dependent_builder(&*owner_ptr));
+                build_span.exit();
                 ::core::mem::forget(drop_guard);
+                $crate::_live_cells!(inc);
 
                 Self {
                     unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell::new(
@@ -418,7 +981,7 @@ macro_rules! self_cell {
                 let layout = $crate::alloc::alloc::Layout::new::<JoinedCell>();
                 assert!(layout.size() != 0);
 
-                let joined_void_ptr = NonNull::new($crate::alloc::alloc::alloc(layout)).unwrap();
+                let joined_void_ptr = $crate::unsafe_self_cell::check_alloc($crate::_heap_profiling!(alloc layout), layout);
 
                 let mut joined_ptr = joined_void_ptr.cast::<JoinedCell>();
 
@@ -431,10 +994,15 @@ macro_rules! self_cell {
                 let mut drop_guard =
                     $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr);
 
-                match dependent_builder(&*owner_ptr) {
+                let build_span = $crate::unsafe_self_cell::BuildSpan::enter(stringify!($StructName));
+                let dependent_result = dependent_builder(&*owner_ptr);
+                build_span.exit();
+
+                match dependent_result {
                     ::core::result::Result::Ok(dependent) => {
                         dependent_ptr.write(dependent);
                         ::core::mem::forget(drop_guard);
+                        $crate::_live_cells!(inc);
 
                         ::core::result::Result::Ok(Self {
                             unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell::new(
@@ -467,7 +1035,7 @@ macro_rules! self_cell {
                 let layout = $crate::alloc::alloc::Layout::new::<JoinedCell>();
                 assert!(layout.size() != 0);
 
-                let joined_void_ptr = NonNull::new($crate::alloc::alloc::alloc(layout)).unwrap();
+                let joined_void_ptr = $crate::unsafe_self_cell::check_alloc($crate::_heap_profiling!(alloc layout), layout);
 
                 let mut joined_ptr = joined_void_ptr.cast::<JoinedCell>();
 
@@ -480,10 +1048,15 @@ macro_rules! self_cell {
                 let mut drop_guard =
                     $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr);
 
-                match dependent_builder(&*owner_ptr) {
+                let build_span = $crate::unsafe_self_cell::BuildSpan::enter(stringify!($StructName));
+                let dependent_result = dependent_builder(&*owner_ptr);
+                build_span.exit();
+
+                match dependent_result {
                     ::core::result::Result::Ok(dependent) => {
                         dependent_ptr.write(dependent);
                         ::core::mem::forget(drop_guard);
+                        $crate::_live_cells!(inc);
 
                         ::core::result::Result::Ok(Self {
                             unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell::new(
@@ -510,7 +1083,16 @@ macro_rules! self_cell {
             }
         }
 
+        $crate::_async_constructors!(
+            $Vis,
+            $StructName,
+            $Owner,
+            $Dependent
+            $(, $OwnerLifetime)?
+        );
+
         /// Borrows owner.
+        #[must_use]
         $Vis fn borrow_owner<'_q>(&'_q self) -> &'_q $Owner {
             unsafe { self.unsafe_self_cell.borrow_owner::<$Dependent<'_q>>() }
         }
@@ -540,8 +1122,146 @@ macro_rules! self_cell {
             func(owner, dependent)
         }
 
+        /// Replaces the dependent with a new one built by `dependent_builder`,
+        /// and calls `consume_old` with the previous dependent by value.
+        ///
+        /// The new dependent is built before the previous one is handed out,
+        /// so both can exist at the same time.
+        $Vis fn replace_dependent_with<Ret>(
+            &mut self,
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> $Dependent<'_q>,
+            consume_old: impl for<'_q> ::core::ops::FnOnce($Dependent<'_q>) -> Ret
+        ) -> Ret {
+            // Same reasoning as with_dependent_mut. Going through a closure
+            // passed to with_dependent_mut here trips up lifetime inference
+            // of older rustc versions for owners with lifetime.
+            let (owner, dependent) = unsafe {
+                self.unsafe_self_cell.borrow_mut()
+            };
+
+            let rebuild_span = $crate::unsafe_self_cell::BuildSpan::enter_rebuild(
+                stringify!($StructName),
+                "replace_dependent_with",
+            );
+            let new_dependent = dependent_builder(owner);
+            rebuild_span.exit();
+
+            let old_dependent = ::core::mem::replace(dependent, new_dependent);
+            consume_old(old_dependent)
+        }
+
+        /// Replaces the dependent with the result of `func`, which takes the
+        /// previous dependent by value, eg. to turn an AST into an optimized
+        /// AST borrowing the same owner.
+        ///
+        /// If `func` panics, the process aborts, because the cell has no valid
+        /// dependent to continue with.
+        $Vis fn map_dependent(
+            &mut self,
+            func: impl for<'_q> ::core::ops::FnOnce($Dependent<'_q>) -> $Dependent<'_q>
+        ) {
+            let _rebuild_span = $crate::unsafe_self_cell::BuildSpan::enter_rebuild(
+                stringify!($StructName),
+                "map_dependent",
+            );
+
+            unsafe { self.unsafe_self_cell.map_dependent::<$Dependent, _>(func) }
+        }
+
+        /// Drops the dependent, calls `func` with an unique reference to the
+        /// owner and builds a new dependent with `dependent_builder` from the
+        /// changed owner. The allocation of the cell is reused.
+        ///
+        /// If `func` or `dependent_builder` panic, the process aborts, because
+        /// the cell has no valid dependent to continue with.
+        $Vis fn with_owner_mut<Ret>(
+            &mut self,
+            func: impl ::core::ops::FnOnce(&mut $Owner) -> Ret,
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> $Dependent<'_q>
+        ) -> Ret {
+            let _rebuild_span = $crate::unsafe_self_cell::BuildSpan::enter_rebuild(
+                stringify!($StructName),
+                "with_owner_mut",
+            );
+
+            unsafe {
+                self.unsafe_self_cell.with_owner_mut::<$Dependent, Ret, _, _>(
+                    func,
+                    |owner| dependent_builder(&*owner)
+                )
+            }
+        }
+
+        /// Replaces the owner with `new_owner`, builds a new dependent for it
+        /// with `dependent_builder` and returns the previous owner. The
+        /// allocation of the cell is reused.
+        ///
+        /// If `dependent_builder` panics, the process aborts, same as with
+        /// `with_owner_mut`.
+        $Vis fn replace_owner(
+            &mut self,
+            new_owner: $Owner,
+            dependent_builder: impl for<'_q> ::core::ops::FnOnce(&'_q $Owner) -> $Dependent<'_q>
+        ) -> $Owner {
+            let _rebuild_span = $crate::unsafe_self_cell::BuildSpan::enter_rebuild(
+                stringify!($StructName),
+                "replace_owner",
+            );
+
+            unsafe {
+                self.unsafe_self_cell.with_owner_mut::<$Dependent, $Owner, _, _>(
+                    |owner| ::core::mem::replace(owner, new_owner),
+                    |owner| dependent_builder(&*owner)
+                )
+            }
+        }
+
         $crate::_covariant_access!($Covariance, $Vis, $Dependent);
 
+        $crate::_layout_dump!($Vis, $StructName, $Owner, $Dependent);
+
+        $crate::_live_cells!(impl $Vis);
+
+        $crate::_heap_profiling!(impl);
+
+        /// Consumes `self` and returns a pointer to the heap allocation
+        /// holding owner and dependent, eg. to pass the cell through FFI as
+        /// opaque handle. The cell is leaked until the pointer is passed to
+        /// [`Self::from_raw`].
+        ///
+        /// The returned pointer must be passed back to [`Self::from_raw`]
+        /// eventually, otherwise owner and dependent are never dropped and the
+        /// allocation is never freed.
+        #[must_use]
+        $Vis fn into_raw(self) -> *mut () {
+            // See into_owner.
+            let unsafe_self_cell = unsafe { ::core::mem::transmute::<
+                Self,
+                $crate::unsafe_self_cell::UnsafeSelfCell<
+                    $StructName$(<$OwnerLifetime>)?,
+                    $Owner,
+                    $Dependent<'static>
+                >
+            >(self) };
+
+            unsafe_self_cell.into_raw() as *mut ()
+        }
+
+        /// Reconstructs a cell from a pointer returned by [`Self::into_raw`].
+        ///
+        /// # Safety
+        ///
+        /// `raw` has to be returned by `into_raw` of the same cell type, and
+        /// each pointer may only be passed to `from_raw` once.
+        $Vis unsafe fn from_raw(raw: *mut ()) -> Self {
+            Self {
+                unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell::new(
+                    ::core::ptr::NonNull::new_unchecked(raw as *mut u8)
+                ),
+                $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+            }
+        }
+
         /// Consumes `self` and returns the the owner.
         $Vis fn into_owner(self) -> $Owner {
             // This is only safe to do with repr(transparent).
@@ -554,25 +1274,105 @@ macro_rules! self_cell {
                 >
             >(self) };
 
+            $crate::unsafe_self_cell::trace_event(stringify!($StructName), "into_owner");
+            $crate::_live_cells!(dec);
+
             let owner = unsafe { unsafe_self_cell.into_owner::<$Dependent>() };
 
             owner
         }
+
+        /// Consumes `self`, calls `func` with the dependent by value and
+        /// returns the owner together with the result of `func`.
+        ///
+        /// Allows moving owned data out of the dependent, eg. a list of
+        /// errors, without cloning it. The owner is moved out only after
+        /// `func` returned.
+        $Vis fn into_owner_and_map<Ret>(
+            self,
+            func: impl for<'_q> ::core::ops::FnOnce($Dependent<'_q>) -> Ret
+        ) -> ($Owner, Ret) {
+            // See into_owner.
+            let unsafe_self_cell = unsafe { ::core::mem::transmute::<
+                Self,
+                $crate::unsafe_self_cell::UnsafeSelfCell<
+                    $StructName$(<$OwnerLifetime>)?,
+                    $Owner,
+                    $Dependent<'static>
+                >
+            >(self) };
+
+            $crate::unsafe_self_cell::trace_event(stringify!($StructName), "into_owner");
+            $crate::_live_cells!(dec);
+
+            unsafe { unsafe_self_cell.into_owner_and_map::<$Dependent, Ret, _>(func) }
+        }
     }
 
     impl $(<$OwnerLifetime>)? Drop for $StructName $(<$OwnerLifetime>)? {
         fn drop(&mut self) {
+            $crate::unsafe_self_cell::trace_event(stringify!($StructName), "drop");
+            $crate::_live_cells!(dec);
+
             unsafe {
                 self.unsafe_self_cell.drop_joined::<$Dependent>();
             }
         }
     }
 
+    impl $(<$OwnerLifetime>)? $crate::DynCell for $StructName $(<$OwnerLifetime>)? {
+        type Owner = $Owner;
+
+        fn borrow_owner<'_q>(&'_q self) -> &'_q $Owner {
+            $StructName::borrow_owner(self)
+        }
+    }
+
     // The user has to choose which traits can and should be automatically
     // implemented for the cell.
-    $($(
-        $crate::_impl_automatic_derive!($AutomaticDerive, $StructName);
-    )*)*
+    $crate::_automatic_derives!(
+        $Covariance, $StructName, $Owner, $Dependent;
+        [$($($AutomaticDerive $(($($DeriveArg)*))?),*)?];
+        _, _, _, _, _; $($($AutomaticDerive $(($($DeriveArg)*))?),*)?
+    );
+};
+(
+    mod $ModName:ident {
+        $(#[$StructMeta:meta])*
+        struct $StructName:ident $($Rest:tt)*
+    }
+) => {
+    // A private struct has to be visible to the module containing the sealed
+    // module.
+    mod $ModName {
+        #[allow(unused_imports)]
+        use super::*;
+
+        $crate::self_cell!(
+            $(#[$StructMeta])*
+            pub(super) struct $StructName $($Rest)*
+        );
+    }
+
+    use $ModName::$StructName;
+};
+(
+    mod $ModName:ident {
+        $(#[$StructMeta:meta])*
+        $Vis:vis struct $StructName:ident $($Rest:tt)*
+    }
+) => {
+    mod $ModName {
+        #[allow(unused_imports)]
+        use super::*;
+
+        $crate::self_cell!(
+            $(#[$StructMeta])*
+            $Vis struct $StructName $($Rest)*
+        );
+    }
+
+    $Vis use $ModName::$StructName;
 };
 }
 
@@ -581,6 +1381,7 @@ macro_rules! self_cell {
 macro_rules! _covariant_access {
     (covariant, $Vis:vis, $Dependent:ident) => {
         /// Borrows dependent.
+        #[must_use]
         $Vis fn borrow_dependent<'_q>(&'_q self) -> &'_q $Dependent<'_q> {
             fn _assert_covariance<'x: 'y, 'y>(x: &'y $Dependent<'x>) -> &'y $Dependent<'y> {
                 //  This function only compiles for covariant types.
@@ -601,6 +1402,335 @@ macro_rules! _covariant_access {
     };
 }
 
+/// Checks at compile time that `$Dependent` is covariant over its lifetime.
+///
+/// This is the same check `self_cell!` performs for dependents marked
+/// `#[covariant]`, usable on its own eg. in tests or before deciding on the
+/// covariance marker of a cell.
+///
+/// ```rust
+/// use self_cell::assert_covariance;
+///
+/// struct Ast<'a>(Vec<&'a str>);
+///
+/// assert_covariance!(Ast);
+/// ```
+///
+/// If the type is not covariant, for example `Cell<&'a str>` or `fn(&'a str)`,
+/// compilation fails with a lifetime mismatch error pointing at the macro
+/// invocation. In that case either mark the dependent `#[not_covariant]` and
+/// access it via `with_dependent`, or change the type so that the lifetime only
+/// appears in covariant positions, eg. by replacing `Cell<&'a T>` with `&'a
+/// Cell<T>`.
+///
+/// `$Dependent` follows the same rules as in `self_cell!`, it has to be a
+/// single identifier of a type with one lifetime parameter. Requires rustc 1.37
+/// or newer.
+#[macro_export]
+macro_rules! assert_covariance {
+    ($Dependent:ident $(<'_>)?) => {
+        const _: () = {
+            fn _assert_covariance<'x: 'y, 'y>(x: &'y $Dependent<'x>) -> &'y $Dependent<'y> {
+                //  This function only compiles for covariant types.
+                x // Change the dependent to not_covariant.
+            }
+
+            // Counts as use, so that types only named here don't warn as dead code.
+            let _ = _assert_covariance;
+        };
+    };
+}
+
+// async fn requires rustc 1.39.
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "old_rust"))]
+macro_rules! _async_constructors {
+    (
+        $Vis:vis,
+        $StructName:ident,
+        $Owner:ty,
+        $Dependent:ident
+        $(, $OwnerLifetime:lifetime)?
+    ) => {
+        // The return types spell out the struct name instead of Self, older
+        // rustc versions reject async fn returning Self with a lifetime.
+
+        /// Constructs a new self-referential struct with an async dependent
+        /// builder.
+        ///
+        /// Works like `new`, but awaits the future returned by
+        /// `dependent_builder`. If the returned future is dropped before it
+        /// completes, the owner is dropped and the heap allocation freed.
+        $Vis async fn new_async<Builder>(owner: $Owner, dependent_builder: Builder)
+            -> $StructName $(<$OwnerLifetime>)?
+        where
+            Builder: for<'_q> $crate::unsafe_self_cell::AsyncBuilder<
+                &'_q $Owner,
+                Output = $Dependent<'_q>
+            >,
+        {
+            use ::core::ptr::NonNull;
+
+            type JoinedCell<'_q $(, $OwnerLifetime)?> =
+                $crate::unsafe_self_cell::JoinedCell<$Owner, $Dependent<'_q>>;
+
+            // See fn new for more explanation. In addition only the drop guard
+            // is kept alive across the await point. Should the future be
+            // dropped while awaiting the dependent, the drop guard cleans up
+            // the owner and the allocation, after the builder future which
+            // borrows the owner has been dropped.
+            let drop_guard = unsafe {
+                let layout = $crate::alloc::alloc::Layout::new::<JoinedCell>();
+                assert!(layout.size() != 0);
+
+                let joined_void_ptr = $crate::unsafe_self_cell::check_alloc($crate::_heap_profiling!(alloc layout), layout);
+
+                let joined_ptr = joined_void_ptr.cast::<JoinedCell>();
+
+                let (owner_ptr, _) = JoinedCell::_field_pointers(joined_ptr.as_ptr());
+
+                // Move owner into newly allocated space.
+                owner_ptr.write(owner);
+
+                $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr)
+            };
+
+            // Borrowing drop_guard in the await expression would keep the
+            // borrow alive across the await for older rustc versions, and
+            // with that require the guard to be Sync for the future to be
+            // Send.
+            let owner = unsafe { drop_guard.owner() };
+            let dependent = dependent_builder.build(owner).await;
+
+            unsafe {
+                let joined_ptr = drop_guard.joined_ptr();
+                let (_, dependent_ptr) = JoinedCell::_field_pointers(joined_ptr.as_ptr());
+
+                // Initialize dependent in final place.
+                dependent_ptr.write(dependent);
+                ::core::mem::forget(drop_guard);
+                $crate::unsafe_self_cell::trace_event(stringify!($StructName), "new_async");
+                $crate::_live_cells!(inc);
+
+                $StructName {
+                    unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell::new(
+                        joined_ptr.cast(),
+                    ),
+                    $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+                }
+            }
+        }
+
+        /// Tries to create a new structure with an async dependent builder.
+        ///
+        /// Consumes owner on error.
+        $Vis async fn try_new_async<Builder, Err>(
+            owner: $Owner,
+            dependent_builder: Builder
+        ) -> ::core::result::Result<$StructName $(<$OwnerLifetime>)?, Err>
+        where
+            Builder: for<'_q> $crate::unsafe_self_cell::AsyncBuilder<
+                &'_q $Owner,
+                Output = ::core::result::Result<$Dependent<'_q>, Err>
+            >,
+        {
+            $StructName::try_new_or_recover_async(owner, dependent_builder)
+                .await
+                .map_err(|(_, err)| err)
+        }
+
+        /// Tries to create a new structure with an async dependent builder.
+        ///
+        /// Returns owner on error.
+        //
+        // Not an async fn, older rustc versions reject async fn with an owner
+        // like fn(&'a ()) in the return type.
+        $Vis fn try_new_or_recover_async<Builder, Err>(
+            owner: $Owner,
+            dependent_builder: Builder
+        ) -> impl ::core::future::Future<
+            Output = ::core::result::Result<$StructName $(<$OwnerLifetime>)?, ($Owner, Err)>
+        >
+        where
+            Builder: for<'_q> $crate::unsafe_self_cell::AsyncBuilder<
+                &'_q $Owner,
+                Output = ::core::result::Result<$Dependent<'_q>, Err>
+            >,
+        {
+            use ::core::ptr::NonNull;
+
+            type JoinedCell<'_q $(, $OwnerLifetime)?> =
+                $crate::unsafe_self_cell::JoinedCell<$Owner, $Dependent<'_q>>;
+
+            async move {
+                let layout = $crate::alloc::alloc::Layout::new::<JoinedCell>();
+
+                // See fn new_async for more explanation.
+                let drop_guard = unsafe {
+                    assert!(layout.size() != 0);
+
+                    let joined_void_ptr = $crate::unsafe_self_cell::check_alloc($crate::_heap_profiling!(alloc layout), layout);
+
+                    let joined_ptr = joined_void_ptr.cast::<JoinedCell>();
+
+                    let (owner_ptr, _) = JoinedCell::_field_pointers(joined_ptr.as_ptr());
+
+                    // Move owner into newly allocated space.
+                    owner_ptr.write(owner);
+
+                    $crate::unsafe_self_cell::OwnerAndCellDropGuard::new(joined_ptr)
+                };
+
+                let owner = unsafe { drop_guard.owner() };
+                let dependent_result = dependent_builder.build(owner).await;
+
+                unsafe {
+                    let joined_ptr = drop_guard.joined_ptr();
+                    let (owner_ptr, dependent_ptr) = JoinedCell::_field_pointers(joined_ptr.as_ptr());
+
+                    match dependent_result {
+                        ::core::result::Result::Ok(dependent) => {
+                            dependent_ptr.write(dependent);
+                            ::core::mem::forget(drop_guard);
+                            $crate::unsafe_self_cell::trace_event(
+                                stringify!($StructName),
+                                "try_new_or_recover_async",
+                            );
+                            $crate::_live_cells!(inc);
+
+                            ::core::result::Result::Ok($StructName {
+                                unsafe_self_cell: $crate::unsafe_self_cell::UnsafeSelfCell::new(
+                                    joined_ptr.cast(),
+                                ),
+                                $(owner_marker: $crate::_covariant_owner_marker_ctor!($OwnerLifetime) ,)?
+                            })
+                        }
+                        ::core::result::Result::Err(err) => {
+                            // See fn try_new_or_recover. The builder future has
+                            // completed and was dropped, nothing borrows owner
+                            // anymore.
+                            let owner_on_err = ::core::ptr::read(owner_ptr);
+
+                            // Allowing drop_guard to finish would let it double free owner.
+                            // So we dealloc the JoinedCell here manually.
+                            ::core::mem::forget(drop_guard);
+                            $crate::alloc::alloc::dealloc(joined_ptr.as_ptr().cast(), layout);
+
+                            ::core::result::Result::Err((owner_on_err, err))
+                        }
+                    }
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "old_rust")]
+macro_rules! _async_constructors {
+    (
+        $Vis:vis,
+        $StructName:ident,
+        $Owner:ty,
+        $Dependent:ident
+        $(, $OwnerLifetime:lifetime)?
+    ) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "layout_dump")]
+macro_rules! _layout_dump {
+    ($Vis:vis, $StructName:ident, $Owner:ty, $Dependent:ident) => {
+        /// Returns the layout of the joined allocation, with addresses, sizes
+        /// and offsets of owner and dependent. Print it with `{}` to get a
+        /// human readable diagram.
+        ///
+        /// Only available with the "layout_dump" feature.
+        $Vis fn layout_dump(&self) -> $crate::LayoutDump {
+            unsafe {
+                self.unsafe_self_cell.layout_dump::<$Dependent>(
+                    stringify!($StructName),
+                    stringify!($Owner),
+                    stringify!($Dependent),
+                )
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "layout_dump"))]
+macro_rules! _layout_dump {
+    ($Vis:vis, $StructName:ident, $Owner:ty, $Dependent:ident) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "live_cells")]
+macro_rules! _live_cells {
+    (impl $Vis:vis) => {
+        /// Returns the number of cells of this type that are currently alive.
+        ///
+        /// Only available with the "live_cells" feature.
+        $Vis fn live_cells() -> usize {
+            Self::_live_cells_counter().load($crate::unsafe_self_cell::Ordering::Relaxed)
+        }
+
+        fn _live_cells_counter() -> &'static $crate::unsafe_self_cell::AtomicUsize {
+            static LIVE_CELLS: $crate::unsafe_self_cell::AtomicUsize =
+                $crate::unsafe_self_cell::AtomicUsize::new(0);
+
+            &LIVE_CELLS
+        }
+    };
+    (inc) => {
+        Self::_live_cells_counter().fetch_add(1, $crate::unsafe_self_cell::Ordering::Relaxed);
+    };
+    (dec) => {
+        Self::_live_cells_counter().fetch_sub(1, $crate::unsafe_self_cell::Ordering::Relaxed);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "live_cells"))]
+macro_rules! _live_cells {
+    (impl $Vis:vis) => {};
+    (inc) => {};
+    (dec) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "heap_profiling")]
+macro_rules! _heap_profiling {
+    (impl) => {
+        // Never inlined, so heap profilers see a frame named after the cell type
+        // as the allocation site.
+        #[inline(never)]
+        unsafe fn _alloc_joined(layout: $crate::alloc::alloc::Layout) -> *mut u8 {
+            $crate::alloc::alloc::alloc(layout)
+        }
+    };
+    (alloc $layout:expr) => {
+        Self::_alloc_joined($layout)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "heap_profiling"))]
+macro_rules! _heap_profiling {
+    (impl) => {};
+    (alloc $layout:expr) => {
+        $crate::alloc::alloc::alloc($layout)
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! _covariant_owner_marker {
@@ -630,9 +1760,370 @@ macro_rules! _covariant_owner_marker_ctor {
     };
 }
 
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "serde")]
+macro_rules! _impl_serde {
+    (Serialize, $StructName:ident) => {
+        impl $crate::serde::Serialize for $StructName {
+            fn serialize<S: $crate::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> ::core::result::Result<S::Ok, S::Error> {
+                $crate::serde::Serialize::serialize(self.borrow_owner(), serializer)
+            }
+        }
+    };
+    (Deserialize, $StructName:ident) => {
+        impl<'de> $crate::serde::Deserialize<'de> for $StructName {
+            fn deserialize<D: $crate::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::core::result::Result<Self, D::Error> {
+                let owner = $crate::serde::Deserialize::deserialize(deserializer)?;
+
+                ::core::result::Result::Ok(Self::new(owner, |owner| {
+                    ::core::convert::From::from(owner)
+                }))
+            }
+        }
+    };
+    (Deserialize(try_from), $StructName:ident) => {
+        impl<'de> $crate::serde::Deserialize<'de> for $StructName {
+            fn deserialize<D: $crate::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::core::result::Result<Self, D::Error> {
+                let owner = $crate::serde::Deserialize::deserialize(deserializer)?;
+
+                Self::try_new(owner, |owner| ::core::convert::TryFrom::try_from(owner))
+                    .map_err($crate::serde::de::Error::custom)
+            }
+        }
+    };
+    (Deserialize(migrate = $migrate:path), $StructName:ident) => {
+        impl<'de> $crate::serde::Deserialize<'de> for $StructName {
+            fn deserialize<D: $crate::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::core::result::Result<Self, D::Error> {
+                let owner = $migrate($crate::serde::Deserialize::deserialize(deserializer)?);
+
+                ::core::result::Result::Ok(Self::new(owner, |owner| {
+                    ::core::convert::From::from(owner)
+                }))
+            }
+        }
+    };
+    (Deserialize(try_from, migrate = $migrate:path), $StructName:ident) => {
+        impl<'de> $crate::serde::Deserialize<'de> for $StructName {
+            fn deserialize<D: $crate::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::core::result::Result<Self, D::Error> {
+                let owner = $migrate($crate::serde::Deserialize::deserialize(deserializer)?);
+
+                Self::try_new(owner, |owner| ::core::convert::TryFrom::try_from(owner))
+                    .map_err($crate::serde::de::Error::custom)
+            }
+        }
+    };
+    (Serialize($($module:ident)::+), $StructName:ident) => {
+        impl $crate::serde::Serialize for $StructName {
+            fn serialize<S: $crate::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> ::core::result::Result<S::Ok, S::Error> {
+                $($module)::+::serialize(self.borrow_owner(), serializer)
+            }
+        }
+    };
+    (Deserialize($($module:ident)::+), $StructName:ident) => {
+        impl<'de> $crate::serde::Deserialize<'de> for $StructName {
+            fn deserialize<D: $crate::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::core::result::Result<Self, D::Error> {
+                let owner = $($module)::+::deserialize(deserializer)?;
+
+                ::core::result::Result::Ok(Self::new(owner, |owner| {
+                    ::core::convert::From::from(owner)
+                }))
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "serde"))]
+macro_rules! _impl_serde {
+    ($x:ident $(($($arg:tt)*))?, $StructName:ident) => {
+        compile_error!(concat!(
+            "Automatic trait impl for trait: ",
+            stringify!($x),
+            " requires the \"serde\" feature of self_cell"
+        ));
+    };
+}
+
+// Implements the automatic derives, unless their combination would compile
+// but be wrong or fail with an unrelated error. Munches the derive list and
+// records which side Eq, PartialEq, Hash and PartialOrd or Ord use, and if
+// Borrow was requested, `_` if they were not requested.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _automatic_derives {
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt; Eq $(, $($rest:tt)*)?
+    ) => {
+        $crate::_automatic_derives!(
+            $Covariance, $StructName, $Owner, $Dependent; $Derives;
+            owner, $partial_eq, $hash, $ord, $borrow; $($($rest)*)?
+        );
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt; Eq($side:ident) $(, $($rest:tt)*)?
+    ) => {
+        $crate::_automatic_derives!(
+            $Covariance, $StructName, $Owner, $Dependent; $Derives;
+            $side, $partial_eq, $hash, $ord, $borrow; $($($rest)*)?
+        );
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt; PartialEq $(, $($rest:tt)*)?
+    ) => {
+        $crate::_automatic_derives!(
+            $Covariance, $StructName, $Owner, $Dependent; $Derives;
+            $eq, owner, $hash, $ord, $borrow; $($($rest)*)?
+        );
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt; PartialEq(owner) $(, $($rest:tt)*)?
+    ) => {
+        $crate::_automatic_derives!(
+            $Covariance, $StructName, $Owner, $Dependent; $Derives;
+            $eq, owner, $hash, $ord, $borrow; $($($rest)*)?
+        );
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt; PartialEq(dependent) $(, $($rest:tt)*)?
+    ) => {
+        $crate::_automatic_derives!(
+            $Covariance, $StructName, $Owner, $Dependent; $Derives;
+            $eq, dependent, $hash, $ord, $borrow; $($($rest)*)?
+        );
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt; Hash $(, $($rest:tt)*)?
+    ) => {
+        $crate::_automatic_derives!(
+            $Covariance, $StructName, $Owner, $Dependent; $Derives;
+            $eq, $partial_eq, owner, $ord, $borrow; $($($rest)*)?
+        );
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt; Hash(owner) $(, $($rest:tt)*)?
+    ) => {
+        $crate::_automatic_derives!(
+            $Covariance, $StructName, $Owner, $Dependent; $Derives;
+            $eq, $partial_eq, owner, $ord, $borrow; $($($rest)*)?
+        );
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt; Hash(dependent) $(, $($rest:tt)*)?
+    ) => {
+        $crate::_automatic_derives!(
+            $Covariance, $StructName, $Owner, $Dependent; $Derives;
+            $eq, $partial_eq, dependent, $ord, $borrow; $($($rest)*)?
+        );
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt; PartialOrd $(, $($rest:tt)*)?
+    ) => {
+        $crate::_automatic_derives!(
+            $Covariance, $StructName, $Owner, $Dependent; $Derives;
+            $eq, $partial_eq, $hash, owner, $borrow; $($($rest)*)?
+        );
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt; Ord $(, $($rest:tt)*)?
+    ) => {
+        $crate::_automatic_derives!(
+            $Covariance, $StructName, $Owner, $Dependent; $Derives;
+            $eq, $partial_eq, $hash, owner, $borrow; $($($rest)*)?
+        );
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt; Borrow $(($($Target:tt)*))? $(, $($rest:tt)*)?
+    ) => {
+        $crate::_automatic_derives!(
+            $Covariance, $StructName, $Owner, $Dependent; $Derives;
+            $eq, $partial_eq, $hash, $ord, borrow; $($($rest)*)?
+        );
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt; $x:ident $(($($arg:tt)*))? $(, $($rest:tt)*)?
+    ) => {
+        $crate::_automatic_derives!(
+            $Covariance, $StructName, $Owner, $Dependent; $Derives;
+            $eq, $partial_eq, $hash, $ord, $borrow; $($($rest)*)?
+        );
+    };
+    (
+        not_covariant, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, dependent, $hash:tt, $ord:tt, $borrow:tt;
+    ) => {
+        compile_error!(concat!(
+            "PartialEq(dependent) is only available for covariant dependents, ",
+            "because dependents with different lifetimes can only be compared ",
+            "through borrow_dependent. Use PartialEq to compare the owners instead."
+        ));
+    };
+    (
+        not_covariant, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        dependent, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt;
+    ) => {
+        compile_error!(concat!(
+            "Eq(dependent) is only available for covariant dependents, ",
+            "because it goes with PartialEq(dependent). Use Eq to compare the ",
+            "owners instead."
+        ));
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        owner, dependent, $hash:tt, $ord:tt, $borrow:tt;
+    ) => {
+        compile_error!(concat!(
+            "Eq requires the owner to be Eq, but PartialEq(dependent) compares ",
+            "the dependent. Use Eq(dependent) to require the dependent to be Eq."
+        ));
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        dependent, owner, $hash:tt, $ord:tt, $borrow:tt;
+    ) => {
+        compile_error!(concat!(
+            "Eq(dependent) requires the dependent to be Eq, but PartialEq compares ",
+            "the owner. Use Eq to require the owner to be Eq."
+        ));
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, dependent, owner, $ord:tt, $borrow:tt;
+    ) => {
+        compile_error!(concat!(
+            "Hash hashes the owner, but PartialEq(dependent) compares the dependent, ",
+            "so equal cells could hash differently. Use Hash(dependent) instead."
+        ));
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, dependent, $hash:tt, owner, $borrow:tt;
+    ) => {
+        compile_error!(concat!(
+            "PartialOrd and Ord compare the owner, but PartialEq(dependent) compares ",
+            "the dependent, so equal cells could be ordered as unequal. Implement ",
+            "them by hand to order the dependents instead."
+        ));
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, dependent, $hash:tt, $ord:tt, borrow;
+    ) => {
+        compile_error!(concat!(
+            "Borrow requires equal cells to have equal owners, but PartialEq(dependent) ",
+            "compares the dependent. Use AsRef to access the owner instead."
+        ));
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident; $Derives:tt;
+        $eq:tt, $partial_eq:tt, dependent, $ord:tt, borrow;
+    ) => {
+        compile_error!(concat!(
+            "Borrow requires cells to hash like their owners, but Hash(dependent) ",
+            "hashes the dependent. Use AsRef to access the owner instead."
+        ));
+    };
+    (
+        $Covariance:ident, $StructName:ident, $Owner:ty, $Dependent:ident;
+        [$($AutomaticDerive:ident $(($($DeriveArg:tt)*))?),*];
+        $eq:tt, $partial_eq:tt, $hash:tt, $ord:tt, $borrow:tt;
+    ) => {
+        $(
+            $crate::_impl_automatic_derive!(
+                $AutomaticDerive $(($($DeriveArg)*))?, $StructName, $Owner, $Dependent
+            );
+        )*
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! _impl_automatic_derive {
+    (Eq, $StructName:ident, $Owner:ty, $Dependent:ident) => {
+        // Bounds on concrete types are checked, so this fails to compile if the
+        // owner is not Eq.
+        impl ::core::cmp::Eq for $StructName where $Owner: ::core::cmp::Eq {}
+    };
+    (Eq(owner), $StructName:ident, $Owner:ty, $Dependent:ident) => {
+        $crate::_impl_automatic_derive!(Eq, $StructName, $Owner, $Dependent);
+    };
+    (Eq(dependent), $StructName:ident, $Owner:ty, $Dependent:ident) => {
+        // Goes with PartialEq(dependent), the dependent has to be Eq instead.
+        impl ::core::cmp::Eq for $StructName where $Dependent<'static>: ::core::cmp::Eq {}
+    };
+    (AsRef, $StructName:ident, $Owner:ty, $Dependent:ident) => {
+        impl ::core::convert::AsRef<$Owner> for $StructName {
+            fn as_ref(&self) -> &$Owner {
+                self.borrow_owner()
+            }
+        }
+    };
+    (AsRef($Target:ty), $StructName:ident, $Owner:ty, $Dependent:ident) => {
+        impl ::core::convert::AsRef<$Target> for $StructName {
+            fn as_ref(&self) -> &$Target {
+                self.borrow_owner()
+            }
+        }
+    };
+    (Borrow, $StructName:ident, $Owner:ty, $Dependent:ident) => {
+        impl ::core::borrow::Borrow<$Owner> for $StructName {
+            fn borrow(&self) -> &$Owner {
+                self.borrow_owner()
+            }
+        }
+    };
+    (Borrow($Target:ty), $StructName:ident, $Owner:ty, $Dependent:ident) => {
+        impl ::core::borrow::Borrow<$Target> for $StructName {
+            fn borrow(&self) -> &$Target {
+                self.borrow_owner()
+            }
+        }
+    };
+    (PartialEq(owner), $StructName:ident, $Owner:ty, $Dependent:ident) => {
+        $crate::_impl_automatic_derive!(PartialEq, $StructName);
+    };
+    (PartialEq(dependent), $StructName:ident, $Owner:ty, $Dependent:ident) => {
+        $crate::_impl_automatic_derive!(PartialEq(dependent), $StructName);
+    };
+    (PartialEq($Target:ty), $StructName:ident, $Owner:ty, $Dependent:ident) => {
+        impl ::core::cmp::PartialEq<$Target> for $StructName {
+            fn eq(&self, other: &$Target) -> bool {
+                *self.borrow_owner() == *other
+            }
+        }
+    };
+    ($x:ident $(($($arg:tt)*))?, $StructName:ident, $Owner:ty, $Dependent:ident) => {
+        $crate::_impl_automatic_derive!($x $(($($arg)*))?, $StructName);
+    };
     (Debug, $StructName:ident) => {
         impl ::core::fmt::Debug for $StructName {
             fn fmt(
@@ -655,9 +2146,17 @@ macro_rules! _impl_automatic_derive {
             }
         }
     };
-    (Eq, $StructName:ident) => {
-        // TODO this should only be allowed if owner is Eq.
-        impl ::core::cmp::Eq for $StructName {}
+    (PartialEq(owner), $StructName:ident) => {
+        $crate::_impl_automatic_derive!(PartialEq, $StructName);
+    };
+    (PartialEq(dependent), $StructName:ident) => {
+        // Dependents with different lifetimes can only be compared if they are
+        // covariant, borrow_dependent is not available otherwise.
+        impl ::core::cmp::PartialEq for $StructName {
+            fn eq(&self, other: &Self) -> bool {
+                *self.borrow_dependent() == *other.borrow_dependent()
+            }
+        }
     };
     (Hash, $StructName:ident) => {
         impl ::core::hash::Hash for $StructName {
@@ -666,12 +2165,208 @@ macro_rules! _impl_automatic_derive {
             }
         }
     };
+    (Hash(owner), $StructName:ident) => {
+        $crate::_impl_automatic_derive!(Hash, $StructName);
+    };
+    (Hash(dependent), $StructName:ident) => {
+        impl ::core::hash::Hash for $StructName {
+            fn hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+                self.with_dependent(|_, dependent| ::core::hash::Hash::hash(dependent, state));
+            }
+        }
+    };
+    (PartialOrd, $StructName:ident) => {
+        impl ::core::cmp::PartialOrd for $StructName {
+            fn partial_cmp(&self, other: &Self) -> ::core::option::Option<::core::cmp::Ordering> {
+                ::core::cmp::PartialOrd::partial_cmp(self.borrow_owner(), other.borrow_owner())
+            }
+        }
+    };
+    (Ord, $StructName:ident) => {
+        impl ::core::cmp::Ord for $StructName {
+            fn cmp(&self, other: &Self) -> ::core::cmp::Ordering {
+                ::core::cmp::Ord::cmp(self.borrow_owner(), other.borrow_owner())
+            }
+        }
+    };
+    (Default, $StructName:ident) => {
+        impl ::core::default::Default for $StructName {
+            fn default() -> Self {
+                Self::new(::core::default::Default::default(), |owner| {
+                    ::core::convert::From::from(owner)
+                })
+            }
+        }
+    };
+    (Display, $StructName:ident) => {
+        impl ::core::fmt::Display for $StructName {
+            fn fmt(
+                &self,
+                fmt: &mut ::core::fmt::Formatter,
+            ) -> ::core::result::Result<(), ::core::fmt::Error> {
+                self.with_dependent(|_, dependent| ::core::fmt::Display::fmt(dependent, fmt))
+            }
+        }
+    };
+    (Display(dependent), $StructName:ident) => {
+        $crate::_impl_automatic_derive!(Display, $StructName);
+    };
+    (Display(owner), $StructName:ident) => {
+        impl ::core::fmt::Display for $StructName {
+            fn fmt(
+                &self,
+                fmt: &mut ::core::fmt::Formatter,
+            ) -> ::core::result::Result<(), ::core::fmt::Error> {
+                ::core::fmt::Display::fmt(self.borrow_owner(), fmt)
+            }
+        }
+    };
+    (Error, $StructName:ident) => {
+        impl ::std::error::Error for $StructName {
+            fn source(&self) -> ::core::option::Option<&(dyn ::std::error::Error + 'static)> {
+                // See BufRead::fill_buf, the same lifetime inference issue of
+                // older rustc versions applies here.
+                let source = self.with_dependent(|_, dependent| {
+                    ::std::error::Error::source(dependent)
+                        .map(|source| source as *const (dyn ::std::error::Error + 'static))
+                });
+
+                source.map(|source| unsafe { &*source })
+            }
+        }
+    };
+    (Read, $StructName:ident) => {
+        impl ::std::io::Read for $StructName {
+            fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+                self.with_dependent_mut(|_, dependent| ::std::io::Read::read(dependent, buf))
+            }
+        }
+    };
+    (BufRead, $StructName:ident) => {
+        impl ::std::io::BufRead for $StructName {
+            fn fill_buf(&mut self) -> ::std::io::Result<&[u8]> {
+                // Older rustc versions fail to infer the lifetime of the slice
+                // when returning it from the closure directly. The slice
+                // borrows from the dependent for as long as self is borrowed
+                // by this function, same as with_dependent_mut.
+                let buf = self.with_dependent_mut(|_, dependent| {
+                    ::std::io::BufRead::fill_buf(dependent).map(|buf| buf as *const [u8])
+                });
+
+                buf.map(|buf| unsafe { &*buf })
+            }
+
+            fn consume(&mut self, amt: usize) {
+                self.with_dependent_mut(|_, dependent| ::std::io::BufRead::consume(dependent, amt))
+            }
+        }
+    };
+    (Write, $StructName:ident) => {
+        impl ::std::io::Write for $StructName {
+            fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+                self.with_dependent_mut(|_, dependent| ::std::io::Write::write(dependent, buf))
+            }
+
+            fn flush(&mut self) -> ::std::io::Result<()> {
+                self.with_dependent_mut(|_, dependent| ::std::io::Write::flush(dependent))
+            }
+        }
+    };
+    (Serialize, $StructName:ident) => {
+        $crate::_impl_serde!(Serialize, $StructName);
+    };
+    (Deserialize, $StructName:ident) => {
+        $crate::_impl_serde!(Deserialize, $StructName);
+    };
+    (Deserialize(from), $StructName:ident) => {
+        $crate::_impl_serde!(Deserialize, $StructName);
+    };
+    (Deserialize(try_from), $StructName:ident) => {
+        $crate::_impl_serde!(Deserialize(try_from), $StructName);
+    };
+    (Deserialize(migrate = $migrate:path), $StructName:ident) => {
+        $crate::_impl_serde!(Deserialize(migrate = $migrate), $StructName);
+    };
+    (Deserialize(try_from, migrate = $migrate:path), $StructName:ident) => {
+        $crate::_impl_serde!(Deserialize(try_from, migrate = $migrate), $StructName);
+    };
+    (Serialize($($module:ident)::+), $StructName:ident) => {
+        $crate::_impl_serde!(Serialize($($module)::+), $StructName);
+    };
+    (Deserialize($($module:ident)::+), $StructName:ident) => {
+        $crate::_impl_serde!(Deserialize($($module)::+), $StructName);
+    };
+    (Clone, $StructName:ident) => {
+        compile_error!(concat!(
+            "No automatic trait impl for trait: Clone. ",
+            "The dependent builder is not stored in the cell, so a clone can't ",
+            "rebuild its dependent. Implement Clone by hand instead, eg. call ",
+            stringify!($StructName),
+            "::new(self.borrow_owner().clone(), builder) with the same builder ",
+            "used to construct the cell. If all cells use the same builder ",
+            "function, use Clone(builder = function). If the dependent implements ",
+            "From<&Owner>, use Clone(from) to rebuild it with From."
+        ));
+    };
+    (Clone(from), $StructName:ident) => {
+        impl ::core::clone::Clone for $StructName {
+            fn clone(&self) -> Self {
+                Self::new(::core::clone::Clone::clone(self.borrow_owner()), |owner| {
+                    ::core::convert::From::from(owner)
+                })
+            }
+        }
+    };
+    (Clone(builder = $builder:path), $StructName:ident) => {
+        impl ::core::clone::Clone for $StructName {
+            fn clone(&self) -> Self {
+                Self::new(::core::clone::Clone::clone(self.borrow_owner()), |owner| {
+                    $builder(owner)
+                })
+            }
+        }
+    };
+    (Clone(try_from), $StructName:ident) => {
+        impl ::core::clone::Clone for $StructName {
+            fn clone(&self) -> Self {
+                let cloned =
+                    Self::try_new(::core::clone::Clone::clone(self.borrow_owner()), |owner| {
+                        ::core::convert::TryFrom::try_from(owner)
+                    });
+
+                match cloned {
+                    ::core::result::Result::Ok(cloned) => cloned,
+                    ::core::result::Result::Err(_) => panic!(concat!(
+                        "Cloning ",
+                        stringify!($StructName),
+                        " failed, TryFrom<&Owner> of the dependent returned an error ",
+                        "for an owner it accepted before"
+                    )),
+                }
+            }
+        }
+    };
     ($x:ident, $StructName:ident) => {
         compile_error!(concat!(
             "No automatic trait impl for trait: ",
             stringify!($x)
         ));
     };
+    ($x:ident($($arg:tt)*), $StructName:ident) => {
+        compile_error!(concat!(
+            "No automatic trait impl for trait: ",
+            stringify!($x),
+            "(",
+            stringify!($($arg)*),
+            ")"
+        ));
+    };
 }
 
-pub use unsafe_self_cell::MutBorrow;
+pub use unsafe_self_cell::{DynCell, MutBorrow};
+
+#[cfg(not(feature = "old_rust"))]
+pub use unsafe_self_cell::AsyncBuilder;
+
+#[cfg(feature = "layout_dump")]
+pub use unsafe_self_cell::{FieldLayout, LayoutDump};
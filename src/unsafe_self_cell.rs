@@ -1,10 +1,18 @@
 #![allow(clippy::needless_lifetimes)]
 
 use core::cell::UnsafeCell;
+#[cfg(not(feature = "old_rust"))]
+use core::future::Future;
 use core::marker::PhantomData;
 use core::mem;
 use core::ptr::{drop_in_place, read, NonNull};
-use core::sync::atomic::{AtomicBool, Ordering};
+
+// Targets like thumbv6m lack atomic swap and fetch_add, portable-atomic provides
+// drop-in replacements for them. Ordering is the same type in both cases.
+#[cfg(not(feature = "portable-atomic"))]
+pub use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(feature = "portable-atomic")]
+pub use portable_atomic::{AtomicBool, AtomicUsize, Ordering};
 
 extern crate alloc;
 
@@ -17,7 +25,7 @@ use alloc::alloc::{dealloc, Layout};
 // These are some of the core invariants we require for this to be safe to use.
 //
 // 1. owner is initialized when UnsafeSelfCell is constructed.
-// 2. owner is NEVER changed again.
+// 2. owner is NEVER changed again, unless dependent was dropped before.
 // 3. The pointer to owner and dependent never changes, even when moved.
 // 4. The only access to owner and dependent is as immutable reference.
 // 5. owner lives longer than dependent.
@@ -56,6 +64,12 @@ impl<ContainedIn, Owner, DependentStatic> UnsafeSelfCell<ContainedIn, Owner, Dep
 
     // Calling any of these *unsafe* functions with the wrong Dependent type is UB.
 
+    // UnsafeSelfCell has no Drop, the joined allocation stays alive until
+    // whoever owns the pointer passes it back to new.
+    pub fn into_raw(self) -> *mut u8 {
+        self.joined_void_ptr.as_ptr()
+    }
+
     pub unsafe fn borrow_owner<'a, Dependent>(&'a self) -> &'a Owner {
         let joined_ptr = self.joined_void_ptr.cast::<JoinedCell<Owner, Dependent>>();
 
@@ -81,6 +95,7 @@ impl<ContainedIn, Owner, DependentStatic> UnsafeSelfCell<ContainedIn, Owner, Dep
     }
 
     // Any subsequent use of this struct other than dropping it is UB.
+    #[cfg(not(feature = "erased_drop"))]
     pub unsafe fn drop_joined<Dependent>(&mut self) {
         let joined_ptr = self.joined_void_ptr.cast::<JoinedCell<Owner, Dependent>>();
 
@@ -90,13 +105,137 @@ impl<ContainedIn, Owner, DependentStatic> UnsafeSelfCell<ContainedIn, Owner, Dep
         // IMPORTANT dependent must be dropped before owner.
         // We don't want to rely on an implicit order of struct fields.
         // So we drop the struct, field by field manually.
-        drop_in_place(&mut (*joined_ptr.as_ptr()).dependent);
+        // Dependents without drop glue, eg. `&str` or `[&str; 4]`, skip this.
+        if mem::needs_drop::<Dependent>() {
+            drop_in_place(&mut (*joined_ptr.as_ptr()).dependent);
+        }
 
         // Dropping owner
         // and deallocating
         // due to _guard at end of scope.
     }
 
+    // Same as above, but only the field drops are monomorphized per cell type.
+    #[cfg(feature = "erased_drop")]
+    pub unsafe fn drop_joined<Dependent>(&mut self) {
+        let drop_dependent = if mem::needs_drop::<Dependent>() {
+            Some(drop_dependent_erased::<Owner, Dependent> as unsafe fn(*mut u8))
+        } else {
+            None
+        };
+
+        drop_joined_erased(
+            self.joined_void_ptr.as_ptr(),
+            Layout::new::<JoinedCell<Owner, Dependent>>(),
+            drop_owner_erased::<Owner, Dependent>,
+            drop_dependent,
+        );
+    }
+
+    #[cfg(feature = "layout_dump")]
+    pub unsafe fn layout_dump<Dependent>(
+        &self,
+        struct_name: &'static str,
+        owner_name: &'static str,
+        dependent_name: &'static str,
+    ) -> LayoutDump {
+        let layout = Layout::new::<JoinedCell<Owner, Dependent>>();
+        let joined_ptr = self.joined_void_ptr.cast::<JoinedCell<Owner, Dependent>>();
+        let (owner_ptr, dependent_ptr) = JoinedCell::_field_pointers(joined_ptr.as_ptr());
+
+        let address = joined_ptr.as_ptr() as usize;
+
+        LayoutDump {
+            struct_name,
+            address,
+            size: layout.size(),
+            align: layout.align(),
+            owner: FieldLayout {
+                name: owner_name,
+                offset: owner_ptr as usize - address,
+                size: mem::size_of::<Owner>(),
+                align: mem::align_of::<Owner>(),
+            },
+            dependent: FieldLayout {
+                name: dependent_name,
+                offset: dependent_ptr as usize - address,
+                size: mem::size_of::<Dependent>(),
+                align: mem::align_of::<Dependent>(),
+            },
+        }
+    }
+
+    pub unsafe fn into_owner_and_map<Dependent, Ret, Func>(self, func: Func) -> (Owner, Ret)
+    where
+        Func: FnOnce(Dependent) -> Ret,
+    {
+        let joined_ptr = self.joined_void_ptr.cast::<JoinedCell<Owner, Dependent>>();
+
+        // In case func panics, the dependent is dropped by then.
+        let drop_guard = OwnerAndCellDropGuard::new(joined_ptr);
+
+        // Move dependent out, the owner stays in place until func returned.
+        let dependent_ptr: *const Dependent = &(*joined_ptr.as_ptr()).dependent;
+        let ret = func(read(dependent_ptr));
+
+        mem::forget(drop_guard);
+
+        let owner_ptr: *const Owner = &(*joined_ptr.as_ptr()).owner;
+
+        // Move owner out so it can be returned.
+        let owner = read(owner_ptr);
+
+        // Deallocate JoinedCell
+        let layout = Layout::new::<JoinedCell<Owner, Dependent>>();
+        dealloc(self.joined_void_ptr.as_ptr(), layout);
+
+        (owner, ret)
+    }
+
+    // Drops dependent, calls func with the owner and builds a new dependent in
+    // place. There is no valid dependent while func and dependent_builder run,
+    // a panic in either aborts.
+    pub unsafe fn with_owner_mut<Dependent, Ret, Func, Builder>(
+        &mut self,
+        func: Func,
+        dependent_builder: Builder,
+    ) -> Ret
+    where
+        Func: FnOnce(&mut Owner) -> Ret,
+        Builder: FnOnce(*const Owner) -> Dependent,
+    {
+        let joined_ptr = self.joined_void_ptr.cast::<JoinedCell<Owner, Dependent>>();
+        let (owner_ptr, dependent_ptr) = JoinedCell::_field_pointers(joined_ptr.as_ptr());
+
+        let abort_guard = AbortOnUnwind;
+
+        drop_in_place(dependent_ptr);
+
+        let ret = func(&mut *owner_ptr);
+
+        dependent_ptr.write(dependent_builder(owner_ptr));
+
+        mem::forget(abort_guard);
+
+        ret
+    }
+
+    // Moves dependent out and replaces it with the result of func. There is no
+    // valid dependent while func runs, a panic in it aborts.
+    pub unsafe fn map_dependent<Dependent, Func>(&mut self, func: Func)
+    where
+        Func: FnOnce(Dependent) -> Dependent,
+    {
+        let joined_ptr = self.joined_void_ptr.cast::<JoinedCell<Owner, Dependent>>();
+        let (_, dependent_ptr) = JoinedCell::_field_pointers(joined_ptr.as_ptr());
+
+        let abort_guard = AbortOnUnwind;
+
+        dependent_ptr.write(func(read(dependent_ptr)));
+
+        mem::forget(abort_guard);
+    }
+
     pub unsafe fn into_owner<Dependent>(self) -> Owner {
         let joined_ptr = self.joined_void_ptr.cast::<JoinedCell<Owner, Dependent>>();
 
@@ -104,7 +243,9 @@ impl<ContainedIn, Owner, DependentStatic> UnsafeSelfCell<ContainedIn, Owner, Dep
         let drop_guard = OwnerAndCellDropGuard::new(joined_ptr);
 
         // Drop dependent
-        drop_in_place(&mut (*joined_ptr.as_ptr()).dependent);
+        if mem::needs_drop::<Dependent>() {
+            drop_in_place(&mut (*joined_ptr.as_ptr()).dependent);
+        }
 
         mem::forget(drop_guard);
 
@@ -140,6 +281,17 @@ where
 {
 }
 
+// Neither dropping nor using a cell is sound after its dependent was dropped
+// and not rebuilt. Panicking while already unwinding aborts, which works
+// without std.
+struct AbortOnUnwind;
+
+impl Drop for AbortOnUnwind {
+    fn drop(&mut self) {
+        panic!("self_cell: panic while the dependent was not initialized, aborting");
+    }
+}
+
 // This struct is used to safely deallocate only the owner if dependent
 // construction fails.
 //
@@ -153,8 +305,25 @@ impl<Owner, Dependent> OwnerAndCellDropGuard<Owner, Dependent> {
     pub unsafe fn new(joined_ptr: NonNull<JoinedCell<Owner, Dependent>>) -> Self {
         Self { joined_ptr }
     }
+
+    // Async constructors keep only the guard alive across await points, so that
+    // their future stays Send and a cancelled construction cleans up as well.
+
+    pub fn joined_ptr(&self) -> NonNull<JoinedCell<Owner, Dependent>> {
+        self.joined_ptr
+    }
+
+    // The returned lifetime is unbounded, the caller has to ensure the reference
+    // does not outlive the owner.
+    pub unsafe fn owner<'a>(&self) -> &'a Owner {
+        &(*self.joined_ptr.as_ptr()).owner
+    }
 }
 
+// SAFETY: The guard only ever drops the owner and deallocates the JoinedCell,
+// it never touches the dependent.
+unsafe impl<Owner: Send, Dependent> Send for OwnerAndCellDropGuard<Owner, Dependent> {}
+
 impl<Owner, Dependent> Drop for OwnerAndCellDropGuard<Owner, Dependent> {
     fn drop(&mut self) {
         struct DeallocGuard {
@@ -184,6 +353,207 @@ impl<Owner, Dependent> Drop for OwnerAndCellDropGuard<Owner, Dependent> {
     }
 }
 
+// Type-erased teardown used by the "erased_drop" feature. All cell types share
+// one copy of the drop guard and deallocation logic, with the same drop order
+// and panic behavior as OwnerAndCellDropGuard.
+#[cfg(feature = "erased_drop")]
+#[inline(never)]
+unsafe fn drop_joined_erased(
+    joined_ptr: *mut u8,
+    layout: Layout,
+    drop_owner: unsafe fn(*mut u8),
+    drop_dependent: Option<unsafe fn(*mut u8)>,
+) {
+    struct ErasedDropGuard {
+        joined_ptr: *mut u8,
+        layout: Layout,
+        drop_owner: unsafe fn(*mut u8),
+    }
+
+    impl Drop for ErasedDropGuard {
+        fn drop(&mut self) {
+            struct DeallocGuard {
+                ptr: *mut u8,
+                layout: Layout,
+            }
+            impl Drop for DeallocGuard {
+                fn drop(&mut self) {
+                    unsafe { dealloc(self.ptr, self.layout) }
+                }
+            }
+
+            // Deallocate even when drop_owner panics
+            let _guard = DeallocGuard {
+                ptr: self.joined_ptr,
+                layout: self.layout,
+            };
+
+            unsafe { (self.drop_owner)(self.joined_ptr) }
+        }
+    }
+
+    // Also used in case drop_dependent fails
+    let _guard = ErasedDropGuard {
+        joined_ptr,
+        layout,
+        drop_owner,
+    };
+
+    // IMPORTANT dependent must be dropped before owner.
+    if let Some(drop_dependent) = drop_dependent {
+        drop_dependent(joined_ptr);
+    }
+}
+
+#[cfg(feature = "erased_drop")]
+unsafe fn drop_owner_erased<Owner, Dependent>(joined_ptr: *mut u8) {
+    drop_in_place(&mut (*(joined_ptr as *mut JoinedCell<Owner, Dependent>)).owner);
+}
+
+#[cfg(feature = "erased_drop")]
+unsafe fn drop_dependent_erased<Owner, Dependent>(joined_ptr: *mut u8) {
+    drop_in_place(&mut (*(joined_ptr as *mut JoinedCell<Owner, Dependent>)).dependent);
+}
+
+/// Layout of the joined allocation of a cell, returned by the `layout_dump`
+/// function generated with the "layout_dump" feature.
+///
+/// The `Display` implementation prints one line per field and padding, with
+/// byte offsets relative to the start of the allocation:
+///
+/// ```text
+/// AstCell at 0x55d0c6a4fb10, size 48, align 8
+///   0..24   owner      String (size 24, align 8)
+///   24..48  dependent  Ast (size 24, align 8)
+/// ```
+#[cfg(feature = "layout_dump")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LayoutDump {
+    /// Name of the cell type.
+    pub struct_name: &'static str,
+    /// Address of the joined allocation.
+    pub address: usize,
+    /// Size of the joined allocation in bytes.
+    pub size: usize,
+    /// Alignment of the joined allocation in bytes.
+    pub align: usize,
+    /// Layout of the owner within the allocation.
+    pub owner: FieldLayout,
+    /// Layout of the dependent within the allocation.
+    pub dependent: FieldLayout,
+}
+
+/// Layout of the owner or dependent within the joined allocation, see
+/// [`LayoutDump`].
+#[cfg(feature = "layout_dump")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldLayout {
+    /// Type name as written in the macro invocation.
+    pub name: &'static str,
+    /// Offset from the start of the joined allocation in bytes.
+    pub offset: usize,
+    /// Size in bytes.
+    pub size: usize,
+    /// Alignment in bytes.
+    pub align: usize,
+}
+
+#[cfg(feature = "layout_dump")]
+impl core::fmt::Display for LayoutDump {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        writeln!(
+            f,
+            "{} at {:#x}, size {}, align {}",
+            self.struct_name, self.address, self.size, self.align
+        )?;
+
+        let mut fields = [("owner", self.owner), ("dependent", self.dependent)];
+        fields.sort_unstable_by_key(|(_, field)| field.offset);
+
+        let mut offset = 0;
+        for (kind, field) in fields.iter() {
+            if field.offset > offset {
+                writeln!(f, "  {:<7} padding", range(offset, field.offset))?;
+            }
+
+            writeln!(
+                f,
+                "  {:<7} {:<10} {} (size {}, align {})",
+                range(field.offset, field.offset + field.size),
+                kind,
+                field.name,
+                field.size,
+                field.align
+            )?;
+
+            offset = field.offset + field.size;
+        }
+
+        if self.size > offset {
+            writeln!(f, "  {:<7} padding", range(offset, self.size))?;
+        }
+
+        Ok(())
+    }
+}
+
+// Display of Range ignores width, format the range first so that it can be padded.
+#[cfg(feature = "layout_dump")]
+fn range(start: usize, end: usize) -> alloc::string::String {
+    alloc::format!("{}..{}", start, end)
+}
+
+// Routes allocation failure to the allocation error handler, same as Box, so
+// that programs can handle it with their own hook instead of a panic.
+#[doc(hidden)]
+#[inline(always)]
+pub fn check_alloc(joined_void_ptr: *mut u8, layout: Layout) -> NonNull<u8> {
+    match NonNull::new(joined_void_ptr) {
+        Some(joined_void_ptr) => joined_void_ptr,
+        None => alloc::alloc::handle_alloc_error(layout),
+    }
+}
+
+// Instrumentation hooks used by the macro generated code. Without the tracing
+// feature these compile down to nothing.
+#[doc(hidden)]
+pub struct BuildSpan {
+    #[cfg(feature = "tracing")]
+    _entered: tracing::span::EnteredSpan,
+}
+
+impl BuildSpan {
+    #[inline(always)]
+    #[allow(unused_variables)]
+    pub fn enter(struct_name: &'static str) -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            _entered: tracing::trace_span!("self_cell::build", cell = struct_name).entered(),
+        }
+    }
+
+    #[inline(always)]
+    #[allow(unused_variables)]
+    pub fn enter_rebuild(struct_name: &'static str, method: &'static str) -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            _entered: tracing::trace_span!("self_cell::rebuild", cell = struct_name, method)
+                .entered(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn exit(self) {}
+}
+
+#[doc(hidden)]
+#[inline(always)]
+#[allow(unused_variables)]
+pub fn trace_event(struct_name: &'static str, event: &'static str) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(cell = struct_name, event);
+}
+
 // Older versions of rust do not support addr_of_mut!. What we want to do here
 // is to emulate the behavior of that macro by going (incorrectly) via a
 // reference cast. Technically this is UB, but testing does not show the older
@@ -226,6 +596,60 @@ impl<Owner, Dependent> JoinedCell<Owner, Dependent> {
     }
 }
 
+/// Dependent builder for async constructors like `new_async`.
+///
+/// Implemented for all functions that take `&'a Owner` and return a future, that resolves to the
+/// dependent. `OwnerRef` is `&'a Owner`. Because the future borrows the owner, closures are usually not general enough to
+/// satisfy the higher-ranked bound. Use an `async fn` instead:
+///
+/// ```
+/// use self_cell::self_cell;
+///
+/// type Words<'a> = Vec<&'a str>;
+///
+/// self_cell!(
+///     struct WordsCell {
+///         owner: String,
+///
+///         #[covariant]
+///         dependent: Words,
+///     }
+/// );
+///
+/// async fn split_words(owner: &String) -> Words<'_> {
+///     owner.split(' ').collect()
+/// }
+///
+/// async fn build() -> WordsCell {
+///     WordsCell::new_async("a b".into(), split_words).await
+/// }
+/// ```
+#[cfg(not(feature = "old_rust"))]
+pub trait AsyncBuilder<OwnerRef> {
+    /// The value the future resolves to.
+    type Output;
+
+    /// The future that resolves to the dependent.
+    type Future: Future<Output = Self::Output>;
+
+    /// Starts building the dependent.
+    fn build(self, owner: OwnerRef) -> Self::Future;
+}
+
+#[cfg(not(feature = "old_rust"))]
+impl<OwnerRef, Func, Fut> AsyncBuilder<OwnerRef> for Func
+where
+    Func: FnOnce(OwnerRef) -> Fut,
+    Fut: Future,
+{
+    type Output = Fut::Output;
+    type Future = Fut;
+
+    fn build(self, owner: OwnerRef) -> Self::Future {
+        self(owner)
+    }
+}
+
 /// Wrapper type that allows creating a self-referential type that hold a mutable borrow `&mut T`.
 ///
 /// Example usage:
@@ -280,6 +704,7 @@ impl<T> MutBorrow<T> {
     ///
     /// Will panic if called anywhere but in the dependent constructor. Will also panic if called
     /// more than once.
+    #[allow(clippy::mut_from_ref)]
     pub fn borrow_mut(&self) -> &mut T {
         // Ensure this function can only be called once.
         // Relaxed should be fine, because only one thread could ever read `false` anyway,
@@ -309,3 +734,81 @@ impl<T> MutBorrow<T> {
 // reference through `borrow_mut()` no other interaction with the inner value MUST ever be possible
 // while the outer `MutBorrow` is alive.
 unsafe impl<T: Send> Sync for MutBorrow<T> {}
+
+/// Trait implemented by every struct declared with [`self_cell`](crate::self_cell).
+///
+/// It is object-safe, which allows writing code that is generic over cells and storing
+/// heterogeneous cells that share an owner type.
+///
+/// Example usage:
+///
+/// ```
+/// use self_cell::{self_cell, DynCell};
+///
+/// type Words<'a> = Vec<&'a str>;
+/// type Chars<'a> = Vec<char>;
+///
+/// self_cell!(
+///     struct WordsCell {
+///         owner: String,
+///
+///         #[covariant]
+///         dependent: Words,
+///     }
+/// );
+///
+/// self_cell!(
+///     struct CharsCell {
+///         owner: String,
+///
+///         #[covariant]
+///         dependent: Chars,
+///     }
+/// );
+///
+/// let cells: Vec<Box<dyn DynCell<Owner = String>>> = vec![
+///     Box::new(WordsCell::new("a b".into(), |owner| owner.split(' ').collect())),
+///     Box::new(CharsCell::new("xyz".into(), |owner| owner.chars().collect())),
+/// ];
+///
+/// let owners: Vec<&str> = cells.iter().map(|cell| cell.borrow_owner().as_str()).collect();
+/// assert_eq!(owners, ["a b", "xyz"]);
+/// ```
+///
+/// Access to the dependent is not part of this trait, neither directly nor through a callback.
+/// Expressing `$Dependent<'a>` as an associated type requires generic associated types, which are
+/// not available with the minimum supported rustc version. The pre-GAT workaround, a helper trait
+/// with `type Dependent` for every `'a` used in a `for<'a> FnOnce(&'a Owner, &'_ Dependent)`
+/// callback bound, is rejected by rustc 1.51 in the generated impl and crashes it when called from
+/// generic code. Use the inherent `with_dependent` and `borrow_dependent` functions instead, and
+/// pass them into generic code as a closure where needed:
+///
+/// ```
+/// use self_cell::{self_cell, DynCell};
+///
+/// type Words<'a> = Vec<&'a str>;
+///
+/// self_cell!(
+///     struct WordsCell {
+///         owner: String,
+///
+///         #[covariant]
+///         dependent: Words,
+///     }
+/// );
+///
+/// fn describe<C: DynCell<Owner = String>>(cell: &C, parts: impl Fn(&C) -> usize) -> String {
+///     format!("{:?} has {} parts", cell.borrow_owner(), parts(cell))
+/// }
+///
+/// let cell = WordsCell::new("a b c".into(), |owner| owner.split(' ').collect());
+/// let description = describe(&cell, |cell| cell.borrow_dependent().len());
+/// assert_eq!(description, "\"a b c\" has 3 parts");
+/// ```
+pub trait DynCell {
+    /// Type of the owner.
+    type Owner;
+
+    /// Borrows owner.
+    fn borrow_owner<'a>(&'a self) -> &'a Self::Owner;
+}
@@ -0,0 +1,9 @@
+use std::cell::Cell;
+
+use self_cell::assert_covariance;
+
+type NotCovariant<'a> = Cell<&'a String>;
+
+assert_covariance!(NotCovariant);
+
+fn main() {}
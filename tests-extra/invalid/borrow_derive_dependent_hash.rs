@@ -0,0 +1,17 @@
+use self_cell::self_cell;
+
+#[derive(Hash)]
+struct Measurement<'a>(&'a str, u64);
+
+self_cell!(
+    struct MeasurementCell {
+        owner: String,
+
+        #[covariant]
+        dependent: Measurement,
+    }
+
+    impl {PartialEq, Eq, Hash(dependent), Borrow(str)}
+);
+
+fn main() {}
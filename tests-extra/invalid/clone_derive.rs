@@ -0,0 +1,16 @@
+use self_cell::self_cell;
+
+type Dependent<'a> = &'a str;
+
+self_cell!(
+    struct CloneCell {
+        owner: String,
+
+        #[covariant]
+        dependent: Dependent,
+    }
+
+    impl {Clone}
+);
+
+fn main() {}
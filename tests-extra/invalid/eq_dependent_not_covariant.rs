@@ -0,0 +1,19 @@
+use std::cell::Cell;
+
+use self_cell::self_cell;
+
+#[derive(PartialEq, Eq)]
+struct Slot<'a>(Cell<&'a str>);
+
+self_cell!(
+    struct SlotCell {
+        owner: String,
+
+        #[not_covariant]
+        dependent: Slot,
+    }
+
+    impl {Eq(dependent)}
+);
+
+fn main() {}
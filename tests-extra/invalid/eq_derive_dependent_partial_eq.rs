@@ -0,0 +1,17 @@
+use self_cell::self_cell;
+
+#[derive(PartialEq)]
+struct Measurement<'a>(&'a str, f64);
+
+self_cell!(
+    struct MeasurementCell {
+        owner: String,
+
+        #[covariant]
+        dependent: Measurement,
+    }
+
+    impl {PartialEq(dependent), Eq}
+);
+
+fn main() {}
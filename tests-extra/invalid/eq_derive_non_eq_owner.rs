@@ -0,0 +1,16 @@
+use self_cell::self_cell;
+
+type Dependent<'a> = &'a f64;
+
+self_cell!(
+    struct FloatCell {
+        owner: f64,
+
+        #[covariant]
+        dependent: Dependent,
+    }
+
+    impl {PartialEq, Eq}
+);
+
+fn main() {}
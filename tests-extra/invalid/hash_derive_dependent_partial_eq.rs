@@ -0,0 +1,17 @@
+use self_cell::self_cell;
+
+#[derive(PartialEq, Eq, Hash)]
+struct Measurement<'a>(&'a str, u64);
+
+self_cell!(
+    struct MeasurementCell {
+        owner: String,
+
+        #[covariant]
+        dependent: Measurement,
+    }
+
+    impl {PartialEq(dependent), Eq(dependent), Hash}
+);
+
+fn main() {}
@@ -0,0 +1,20 @@
+use self_cell::self_cell;
+
+type Dependent<'a> = &'a str;
+
+self_cell!(
+    mod sealed {
+        struct SealedCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Dependent,
+        }
+    }
+);
+
+fn main() {
+    let cell = SealedCell::new("Crackle that thunder".into(), |owner| owner);
+
+    let _internals = &cell.unsafe_self_cell;
+}
@@ -8,7 +8,7 @@ use crossbeam_utils::thread;
 
 use impls::impls;
 
-use self_cell::{self_cell, MutBorrow};
+use self_cell::{assert_covariance, self_cell, MutBorrow};
 
 #[allow(dead_code)]
 struct NotSend<'a> {
@@ -144,3 +144,391 @@ fn share_across_threads() {
     })
     .unwrap();
 }
+
+#[test]
+fn assert_covariance() {
+    // Checked at compile time, see invalid/assert_covariance.rs for a type that fails it.
+    assert_covariance!(Ast);
+    assert_covariance!(Ast<'_>);
+
+    type Words<'a> = Vec<&'a str>;
+    assert_covariance!(Words);
+}
+
+#[cfg(feature = "serde_integration")]
+#[test]
+fn serde_round_trip() {
+    self_cell!(
+        struct SerdeCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {Serialize, Deserialize}
+    );
+
+    let cell = SerdeCell::new("hy hyperspeed".into(), |owner| owner.into());
+
+    let json = serde_json::to_string(&cell).unwrap();
+    assert_eq!(json, "\"hy hyperspeed\"");
+
+    let cell: SerdeCell = serde_json::from_str(&json).unwrap();
+    assert_eq!(cell.borrow_owner(), "hy hyperspeed");
+    assert_eq!(cell.borrow_dependent(), &Ast(vec!["h", " hypersp"]));
+
+    assert!(serde_json::from_str::<SerdeCell>("3").is_err());
+}
+
+#[cfg(feature = "serde_integration")]
+#[test]
+fn serde_try_from_rejected() {
+    use std::convert::TryFrom;
+
+    #[derive(Debug, PartialEq)]
+    struct Digits<'a>(&'a str);
+
+    impl<'a> TryFrom<&'a String> for Digits<'a> {
+        type Error = String;
+
+        fn try_from(owner: &'a String) -> Result<Self, String> {
+            if owner.chars().all(|c| c.is_ascii_digit()) {
+                Ok(Digits(owner))
+            } else {
+                Err(format!("not a number: {}", owner))
+            }
+        }
+    }
+
+    self_cell!(
+        struct DigitsCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Digits,
+        }
+
+        impl {Deserialize(try_from)}
+    );
+
+    let cell: DigitsCell = serde_json::from_str("\"1312\"").unwrap();
+    assert_eq!(cell.borrow_dependent(), &Digits("1312"));
+
+    let err = serde_json::from_str::<DigitsCell>("\"13a\"").err().unwrap();
+    assert!(err.to_string().contains("not a number: 13a"));
+}
+
+#[cfg(feature = "serde_integration")]
+#[test]
+fn serde_migrate() {
+    use std::convert::TryFrom;
+
+    // Older versions wrote the key as "name".
+    fn rename_key(owner: String) -> String {
+        owner.replacen("name=", "title=", 1)
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Title<'a>(&'a str);
+
+    impl<'a> TryFrom<&'a String> for Title<'a> {
+        type Error = String;
+
+        fn try_from(owner: &'a String) -> Result<Self, String> {
+            match owner.strip_prefix("title=") {
+                Some(title) => Ok(Title(title)),
+                None => Err(format!("no title: {}", owner)),
+            }
+        }
+    }
+
+    self_cell!(
+        struct ConfigCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {Deserialize(migrate = rename_key)}
+    );
+
+    let cell: ConfigCell = serde_json::from_str("\"name=Nebel\"").unwrap();
+    assert_eq!(cell.borrow_owner(), "title=Nebel");
+
+    self_cell!(
+        struct TitleCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Title,
+        }
+
+        impl {Deserialize(try_from, migrate = rename_key)}
+    );
+
+    let cell: TitleCell = serde_json::from_str("\"name=Nebel\"").unwrap();
+    assert_eq!(cell.borrow_dependent(), &Title("Nebel"));
+
+    let cell: TitleCell = serde_json::from_str("\"title=Wolke\"").unwrap();
+    assert_eq!(cell.borrow_dependent(), &Title("Wolke"));
+
+    let err = serde_json::from_str::<TitleCell>("\"Nebel\"").err().unwrap();
+    assert!(err.to_string().contains("no title: Nebel"));
+}
+
+// Serializes bytes as hex string, for the serde with module test.
+#[cfg(all(test, feature = "serde_integration"))]
+mod hex {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2).unwrap_or("?"), 16))
+            .collect::<Result<_, _>>()
+            .map_err(de::Error::custom)
+    }
+}
+
+#[cfg(feature = "serde_integration")]
+#[test]
+fn serde_with_module() {
+    #[derive(Debug, PartialEq)]
+    struct Header<'a>(&'a [u8]);
+
+    impl<'a> From<&'a Vec<u8>> for Header<'a> {
+        fn from(bytes: &'a Vec<u8>) -> Self {
+            Header(&bytes[..bytes.len().min(2)])
+        }
+    }
+
+    self_cell!(
+        struct PacketCell {
+            owner: Vec<u8>,
+
+            #[covariant]
+            dependent: Header,
+        }
+
+        impl {Serialize(hex), Deserialize(crate::hex)}
+    );
+
+    let cell = PacketCell::new(vec![0xca, 0xfe, 0x01], |owner| owner.into());
+
+    let json = serde_json::to_string(&cell).unwrap();
+    assert_eq!(json, "\"cafe01\"");
+
+    let cell: PacketCell = serde_json::from_str(&json).unwrap();
+    assert_eq!(cell.borrow_owner(), &vec![0xca, 0xfe, 0x01]);
+    assert_eq!(cell.borrow_dependent(), &Header(&[0xca, 0xfe]));
+
+    assert!(serde_json::from_str::<PacketCell>("\"cafe0\"").is_err());
+}
+
+#[cfg(test)]
+mod async_construction {
+    use super::*;
+
+    // Minimal executor, so the async constructors can be tested without pulling in a runtime.
+    fn noop_waker() -> std::task::Waker {
+        use std::task::{RawWaker, RawWakerVTable, Waker};
+
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+        unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+    }
+
+    fn poll_once<F: std::future::Future>(
+        future: std::pin::Pin<&mut F>,
+    ) -> std::task::Poll<F::Output> {
+        let waker = noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        future.poll(&mut cx)
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        loop {
+            if let std::task::Poll::Ready(output) = poll_once(future.as_mut()) {
+                return output;
+            }
+        }
+    }
+
+    // Returns Pending exactly once.
+    struct YieldNow(bool);
+
+    impl std::future::Future for YieldNow {
+        type Output = ();
+
+        fn poll(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<()> {
+            if self.0 {
+                std::task::Poll::Ready(())
+            } else {
+                self.0 = true;
+                std::task::Poll::Pending
+            }
+        }
+    }
+
+    async fn ast_from_async(body: &String) -> Ast<'_> {
+        YieldNow(false).await;
+        body.into()
+    }
+
+    #[test]
+    fn new_async() {
+        let body = String::from("async trout swims");
+
+        let cell = block_on(AstCell::new_async(body.clone(), ast_from_async));
+        assert_eq!(cell.borrow_owner(), &body);
+        assert_eq!(cell.borrow_dependent(), &Ast::from(&body));
+
+        fn assert_send<T: Send>(_: &T) {}
+        let future = AstCell::new_async(body.clone(), ast_from_async);
+        assert_send(&future);
+
+        type OwnedBody<'a> = &'a str;
+
+        self_cell!(
+            struct ChildCell<'a> {
+                owner: &'a String,
+
+                #[covariant]
+                dependent: OwnedBody,
+            }
+        );
+
+        async fn child_from_async<'a>(body: &'a &String) -> &'a str {
+            &body[6..11]
+        }
+
+        let child_cell = block_on(ChildCell::new_async(&body, child_from_async));
+        assert_eq!(*child_cell.borrow_dependent(), "trout");
+    }
+
+    #[test]
+    fn try_new_async() {
+        async fn parse_async(body: &String) -> Result<Ast<'_>, i32> {
+            YieldNow(false).await;
+            if body.starts_with('#') {
+                Err(body.len() as i32)
+            } else {
+                Ok(body.into())
+            }
+        }
+
+        let body = String::from("async trout swims");
+
+        let cell = block_on(AstCell::try_new_async(body.clone(), parse_async)).unwrap();
+        assert_eq!(cell.borrow_dependent(), &Ast::from(&body));
+
+        let err = block_on(AstCell::try_new_async("#comment".into(), parse_async));
+        assert_eq!(err.unwrap_err(), 8);
+
+        let cell = block_on(AstCell::try_new_or_recover_async(body.clone(), parse_async)).unwrap();
+        assert_eq!(cell.borrow_owner(), &body);
+
+        let (owner, err) = block_on(AstCell::try_new_or_recover_async(
+            "#comment".into(),
+            parse_async,
+        ))
+        .unwrap_err();
+        assert_eq!(owner, "#comment");
+        assert_eq!(err, 8);
+    }
+
+    #[test]
+    fn new_async_cancelled() {
+        // Dropping the construction future while it waits for the dependent must
+        // drop the builder future first, then the owner, and free the allocation.
+
+        #[derive(Debug, PartialEq)]
+        enum Event {
+            BuilderDropped(String),
+            OwnerDropped,
+        }
+
+        type Events = Rc<RefCell<Vec<Event>>>;
+
+        struct Owner(String, Events);
+
+        impl Drop for Owner {
+            fn drop(&mut self) {
+                self.1.borrow_mut().push(Event::OwnerDropped);
+            }
+        }
+
+        type Dependent<'a> = &'a str;
+
+        self_cell!(
+            struct CancelCell {
+                owner: Owner,
+
+                #[covariant]
+                dependent: Dependent,
+            }
+        );
+
+        // Accesses the owner on drop, which is UB should the owner be gone already.
+        struct BuilderGuard<'a>(&'a Owner);
+
+        impl Drop for BuilderGuard<'_> {
+            fn drop(&mut self) {
+                let owner_str = self.0 .0.clone();
+                self.0
+                     .1
+                    .borrow_mut()
+                    .push(Event::BuilderDropped(owner_str));
+            }
+        }
+
+        async fn slow_builder(owner: &Owner) -> &str {
+            let _guard = BuilderGuard(owner);
+            YieldNow(false).await;
+            &owner.0
+        }
+
+        let events: Events = Rc::default();
+
+        let mut future = Box::pin(CancelCell::new_async(
+            Owner("cancel me".into(), events.clone()),
+            slow_builder,
+        ));
+        assert!(poll_once(future.as_mut()).is_pending());
+        assert!(events.borrow().is_empty());
+
+        drop(future);
+        assert_eq!(
+            *events.borrow(),
+            [
+                Event::BuilderDropped("cancel me".into()),
+                Event::OwnerDropped
+            ]
+        );
+
+        // Never polled at all.
+        drop(CancelCell::new_async(
+            Owner("never polled".into(), events.clone()),
+            slow_builder,
+        ));
+        assert_eq!(events.borrow().len(), 3);
+        assert_eq!(events.borrow()[2], Event::OwnerDropped);
+    }
+}
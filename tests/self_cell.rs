@@ -1,5 +1,8 @@
 // The unsafe being used gets tested with miri in the CI.
 
+// Some tests format a reference to the cell, which also checks Debug through &T.
+#![allow(clippy::useless_borrows_in_formatting)]
+
 use std::cell::Cell;
 use std::cell::RefCell;
 use std::fmt::Debug;
@@ -10,9 +13,9 @@ use std::str;
 
 use once_cell::unsync::OnceCell;
 
-use self_cell::{self_cell, MutBorrow};
+use self_cell::{self_cell, DynCell, MutBorrow};
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Hash)]
 pub struct Ast<'input>(pub Vec<&'input str>);
 
 impl<'x> From<&'x String> for Ast<'x> {
@@ -308,6 +311,94 @@ fn public_cell() {
     pub type PubTy = PubCell;
 }
 
+#[test]
+fn impl_meta() {
+    self_cell!(
+        struct LintCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        #[deny(missing_docs)]
+        #[allow(clippy::needless_lifetimes)]
+        impl {Debug}
+    );
+
+    self_cell!(
+        struct LintCellNoDerive {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        #[deny(unused_must_use)]
+    );
+
+    let cell = LintCell::new("eins zwei".into(), |owner| owner.into());
+    assert_eq!(cell.borrow_dependent(), &Ast(vec!["ns ", "in"]));
+
+    let cell = LintCellNoDerive::new("eins zwei".into(), |owner| owner.into());
+    assert_eq!(cell.borrow_owner(), "eins zwei");
+}
+
+#[test]
+fn elided_dependent_lifetime() {
+    self_cell!(
+        struct ElidedAstCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast<'_>,
+        }
+
+        impl {Debug}
+    );
+
+    let body = String::from("elided trout");
+    let cell = ElidedAstCell::new(body.clone(), |owner| owner.into());
+    assert_eq!(cell.borrow_dependent(), &Ast::from(&body));
+}
+
+#[test]
+fn sealed_cell() {
+    self_cell!(
+        mod sealed_ast_cell {
+            #[doc(hidden)]
+            struct SealedAstCell {
+                owner: String,
+
+                #[covariant]
+                dependent: Ast,
+            }
+
+            impl {Debug, PartialEq}
+        }
+    );
+
+    self_cell!(
+        mod sealed_pub_cell {
+            pub(crate) struct SealedPubCell<'a> {
+                owner: &'a String,
+
+                #[covariant]
+                dependent: Ast,
+            }
+        }
+    );
+
+    let body = String::from("sealed trout");
+    let cell = SealedAstCell::new(body.clone(), |owner| owner.into());
+    assert_eq!(cell.borrow_dependent(), &Ast::from(&body));
+    assert_eq!(cell, SealedAstCell::new(body.clone(), |owner| owner.into()));
+
+    let child_cell = SealedPubCell::new(&body, |owner| Ast::from(*owner));
+    assert_eq!(*child_cell.borrow_owner(), &body);
+    assert_eq!(child_cell.into_owner(), &body);
+}
+
 #[test]
 fn custom_drop() {
     #[derive(Debug, PartialEq, Eq)]
@@ -530,6 +621,74 @@ fn dependent_replace() {
     assert_eq!(ast_cell.borrow_dependent().0, vec!["Eg", "Ega", "in w"]);
 }
 
+#[test]
+fn dependent_replace_with() {
+    let input = String::from("Egal in welchen Farben ihr den ..");
+
+    let mut ast_cell = PackedAstCell::new(input.clone(), |owner| owner.into());
+
+    let old_len = ast_cell.replace_dependent_with(
+        |owner| Ast(vec![&owner[0..2], &owner[5..7], &owner[8..10]]),
+        |old_ast| {
+            assert_eq!(old_ast, Ast::from(&input));
+            old_ast.0.len()
+        },
+    );
+
+    assert_eq!(old_len, 2);
+    assert_eq!(ast_cell.borrow_owner(), &input);
+    assert_eq!(ast_cell.borrow_dependent().0, vec!["Eg", "in", "we"]);
+}
+
+#[test]
+fn dependent_map() {
+    let input = String::from("Egal in welchen Farben ihr den ..");
+
+    let mut ast_cell = PackedAstCell::new(input.clone(), |owner| owner.into());
+
+    ast_cell.map_dependent(|mut ast| {
+        ast.0.retain(|word| word.len() == 3);
+        ast.0.push(&ast.0[0][..2]);
+        ast
+    });
+
+    assert_eq!(ast_cell.borrow_owner(), &input);
+    assert_eq!(ast_cell.borrow_dependent().0, vec!["al ", "al"]);
+}
+
+#[test]
+fn owner_mutate() {
+    let mut ast_cell = PackedAstCell::new("Gegen den Wind".into(), |owner| owner.into());
+    assert_eq!(ast_cell.borrow_dependent().0, vec!["gen", "eg"]);
+
+    let old_len = ast_cell.with_owner_mut(
+        |owner| {
+            let old_len = owner.len();
+            owner.replace_range(..5, "Mit");
+            old_len
+        },
+        |owner| Ast(owner.split(' ').collect()),
+    );
+
+    assert_eq!(old_len, 14);
+    assert_eq!(ast_cell.borrow_owner(), "Mit den Wind");
+    assert_eq!(ast_cell.borrow_dependent().0, vec!["Mit", "den", "Wind"]);
+}
+
+#[test]
+fn owner_replace() {
+    let mut ast_cell = PackedAstCell::new("Gegen den Wind".into(), |owner| owner.into());
+
+    let old_owner = ast_cell.replace_owner("Mit dem Strom".into(), |owner| owner.into());
+
+    assert_eq!(old_owner, "Gegen den Wind");
+    assert_eq!(ast_cell.borrow_owner(), "Mit dem Strom");
+    assert_eq!(
+        ast_cell.borrow_dependent(),
+        &Ast::from(&String::from("Mit dem Strom"))
+    );
+}
+
 #[test]
 fn try_new_or_recover() {
     let original_input = String::from("Ein See aus Schweiß ..");
@@ -583,6 +742,147 @@ fn into_owner() {
     // assert_eq!(ast_cell.borrow_owner(), &expected_body);
 }
 
+#[test]
+fn into_raw_from_raw() {
+    let input = String::from("Steine Steine");
+
+    let ast_cell = PackedAstCell::new(input.clone(), |owner| owner.into());
+    let expected_owner_ptr = ast_cell.borrow_owner() as *const String;
+
+    let raw = ast_cell.into_raw();
+    let ast_cell = unsafe { PackedAstCell::from_raw(raw) };
+
+    assert_eq!(ast_cell.borrow_owner() as *const String, expected_owner_ptr);
+    assert_eq!(ast_cell.borrow_owner(), &input);
+    assert_eq!(ast_cell.borrow_dependent(), &Ast::from(&input));
+}
+
+#[test]
+fn into_owner_and_map() {
+    #[derive(Debug, PartialEq)]
+    struct Checked<'a> {
+        words: Vec<&'a str>,
+        errors: Vec<String>,
+    }
+
+    self_cell!(
+        struct CheckedCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Checked,
+        }
+    );
+
+    let input = String::from("fine bad fine bad");
+
+    let cell = CheckedCell::new(input.clone(), |owner| {
+        let words: Vec<&str> = owner.split(' ').collect();
+        let errors = words
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| **word == "bad")
+            .map(|(i, _)| format!("bad word at {}", i))
+            .collect();
+
+        Checked { words, errors }
+    });
+
+    let (owner, errors) = cell.into_owner_and_map(|checked| {
+        assert_eq!(checked.words.len(), 4);
+        checked.errors
+    });
+
+    assert_eq!(owner, input);
+    assert_eq!(errors, ["bad word at 1", "bad word at 3"]);
+
+    // Panicking in func drops owner and dependent and frees the cell, miri
+    // detects leaks and double drops.
+    let cell = PackedAstCell::new(input.clone(), |owner| owner.into());
+    let result = catch_unwind(move || {
+        cell.into_owner_and_map(|ast| {
+            assert_eq!(ast, Ast::from(&input));
+            panic!("oh no")
+        })
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn boxed_unsized_owner() {
+    type StrRef<'a> = &'a str;
+    type BytesRef<'a> = &'a [u8];
+    type DisplayRef<'a> = &'a dyn std::fmt::Display;
+
+    self_cell!(
+        struct BoxStrCell {
+            owner: Box<str>,
+
+            #[covariant]
+            dependent: StrRef,
+        }
+    );
+
+    self_cell!(
+        struct BoxBytesCell {
+            owner: Box<[u8]>,
+
+            #[covariant]
+            dependent: BytesRef,
+        }
+    );
+
+    self_cell!(
+        struct BoxDynCell {
+            owner: Box<dyn std::fmt::Display>,
+
+            #[covariant]
+            dependent: DisplayRef,
+        }
+    );
+
+    let str_cell = BoxStrCell::new("sailing by".into(), |owner| &owner[2..5]);
+    assert_eq!(&**str_cell.borrow_owner(), "sailing by");
+    assert_eq!(*str_cell.borrow_dependent(), "ili");
+
+    let bytes_cell = BoxBytesCell::new(vec![3, 4, 5].into_boxed_slice(), |owner| &owner[1..]);
+    assert_eq!(*bytes_cell.borrow_dependent(), &[4, 5]);
+    assert_eq!(&*bytes_cell.into_owner(), &[3, 4, 5]);
+
+    let dyn_cell = BoxDynCell::new(Box::new(77), |owner| &**owner);
+    assert_eq!(dyn_cell.borrow_dependent().to_string(), "77");
+}
+
+#[test]
+fn cow_static_owner() {
+    use std::borrow::Cow;
+
+    type StrRef<'a> = &'a str;
+
+    self_cell!(
+        struct CowStrCell {
+            owner: Cow<'static, str>,
+
+            #[covariant]
+            dependent: StrRef,
+        }
+    );
+
+    static SOURCE: &str = "fn main() {}";
+
+    let borrowed_cell = CowStrCell::new(Cow::Borrowed(SOURCE), |owner| &owner[3..7]);
+    assert_eq!(*borrowed_cell.borrow_dependent(), "main");
+    // The dependent points into the static data, it was not copied.
+    assert_eq!(
+        borrowed_cell.borrow_dependent().as_ptr(),
+        SOURCE[3..].as_ptr()
+    );
+
+    let owned_cell = CowStrCell::new(Cow::Owned(SOURCE.to_string()), |owner| &owner[3..7]);
+    assert_eq!(*owned_cell.borrow_dependent(), "main");
+    assert_ne!(owned_cell.borrow_dependent().as_ptr(), SOURCE[3..].as_ptr());
+}
+
 #[test]
 fn zero_size_cell() {
     struct ZeroSizeRef<'a>(PhantomData<&'a ()>);
@@ -728,6 +1028,474 @@ fn debug_impl() {
     assert_eq!(format!("{:#?}", ast_cell), hash_fmt);
 }
 
+#[test]
+fn dependent_eq_hash_impl() {
+    use std::collections::HashSet;
+
+    self_cell!(
+        struct DependentEqCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {PartialEq(dependent), Eq(dependent), Hash(dependent)}
+    );
+
+    // Same owner, different builders.
+    let cell_a = DependentEqCell::new("ab cd".into(), |owner| Ast(vec![&owner[..2]]));
+    let cell_b = DependentEqCell::new("ab cd".into(), |owner| Ast(vec![&owner[3..]]));
+    // Different owners, same dependent.
+    let cell_c = DependentEqCell::new("ab ab".into(), |owner| Ast(vec![&owner[3..]]));
+
+    assert!(cell_a != cell_b);
+    assert!(cell_a == cell_c);
+
+    let cells: HashSet<DependentEqCell> = vec![cell_a, cell_b, cell_c].into_iter().collect();
+    assert_eq!(cells.len(), 2);
+}
+
+#[test]
+fn as_ref_borrow_impl() {
+    use std::borrow::Borrow;
+    use std::collections::HashMap;
+
+    self_cell!(
+        struct OwnerRefCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {PartialEq, Eq, Hash, AsRef, AsRef(str), Borrow, Borrow(str)}
+    );
+
+    let cell = OwnerRefCell::new("Nebel ziehen".into(), |owner| owner.into());
+
+    let owner: &String = cell.as_ref();
+    assert_eq!(owner, "Nebel ziehen");
+    let owner: &str = cell.as_ref();
+    assert_eq!(owner, "Nebel ziehen");
+    let owner: &String = cell.borrow();
+    assert_eq!(owner, "Nebel ziehen");
+
+    let mut cells = HashMap::new();
+    cells.insert(cell, 3);
+    assert_eq!(cells.get("Nebel ziehen"), Some(&3));
+    assert_eq!(cells.get(&String::from("Nebel ziehen")), Some(&3));
+    assert_eq!(cells.get("Nebel"), None);
+}
+
+#[test]
+fn partial_eq_target_impl() {
+    self_cell!(
+        struct OwnerEqCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {PartialEq(String), PartialEq(str)}
+    );
+
+    let cell = OwnerEqCell::new("Nebel ziehen".into(), |owner| owner.into());
+
+    let source = String::from("Nebel ziehen");
+    assert!(cell == source);
+    assert!(cell == *"Nebel ziehen");
+    assert!(cell != *"Nebel");
+}
+
+#[test]
+fn partial_eq_target_type_impl() {
+    type Prefix<'a> = &'a [u8];
+
+    self_cell!(
+        struct BytesCell {
+            owner: Vec<u8>,
+
+            #[covariant]
+            dependent: Prefix,
+        }
+
+        impl {PartialEq(Vec<u8>), PartialEq([u8]), PartialEq(&[u8])}
+    );
+
+    let cell = BytesCell::new(vec![1, 2, 3], |owner| &owner[..1]);
+
+    assert!(cell == vec![1, 2, 3]);
+    assert!(cell == *[1, 2, 3].as_ref());
+    assert!(cell == [1, 2, 3].as_ref());
+    assert!(cell != [1, 2].as_ref());
+
+    self_cell!(
+        struct StrEqCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {PartialEq(&str)}
+    );
+
+    let cell = StrEqCell::new("Nebel ziehen".into(), |owner| owner.into());
+    assert!(cell == "Nebel ziehen");
+    assert!(cell != "Nebel");
+}
+
+#[test]
+fn ord_impl() {
+    use std::collections::BTreeSet;
+
+    self_cell!(
+        struct OrdCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {PartialEq, Eq, PartialOrd, Ord}
+    );
+
+    let cell_b = OrdCell::new("bbbbb".into(), |owner| owner.into());
+    let cell_a = OrdCell::new("aaaaa".into(), |owner| owner.into());
+    assert!(cell_a < cell_b);
+
+    let owners: Vec<String> = vec![cell_b, cell_a]
+        .into_iter()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|cell| cell.into_owner())
+        .collect();
+    assert_eq!(owners, ["aaaaa", "bbbbb"]);
+}
+
+#[test]
+fn default_impl() {
+    #[derive(Debug, PartialEq)]
+    struct Words<'a>(Vec<&'a str>);
+
+    impl<'a> From<&'a String> for Words<'a> {
+        fn from(owner: &'a String) -> Self {
+            Words(owner.split_whitespace().collect())
+        }
+    }
+
+    self_cell!(
+        struct WordsCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Words,
+        }
+
+        impl {Default}
+    );
+
+    #[derive(Default)]
+    struct Document {
+        words: WordsCell,
+    }
+
+    let document = Document::default();
+    assert_eq!(document.words.borrow_owner(), "");
+    assert_eq!(document.words.borrow_dependent(), &Words(vec![]));
+}
+
+#[test]
+fn clone_from_impl() {
+    self_cell!(
+        struct CloneCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {Debug, PartialEq, Clone(from)}
+    );
+
+    let cell = CloneCell::new("Ohne Dich".into(), |owner| owner.into());
+    let clone = cell.clone();
+
+    assert_eq!(clone, cell);
+    assert_eq!(clone.borrow_dependent(), &Ast(vec!["ne ", "hn"]));
+    // The clone borrows its own owner.
+    assert_ne!(
+        clone.borrow_dependent().0[0].as_ptr(),
+        cell.borrow_dependent().0[0].as_ptr()
+    );
+
+    let mut target = CloneCell::new("Mit Dir".into(), |owner| owner.into());
+    target.clone_from(&cell);
+
+    assert_eq!(target, cell);
+    assert_eq!(target.borrow_dependent(), &Ast(vec!["ne ", "hn"]));
+}
+
+#[test]
+fn clone_from_panic() {
+    #[derive(Debug, PartialEq)]
+    struct NonEmpty<'a>(&'a str);
+
+    impl<'a> From<&'a String> for NonEmpty<'a> {
+        fn from(owner: &'a String) -> Self {
+            assert!(!owner.is_empty(), "empty owner");
+            NonEmpty(owner)
+        }
+    }
+
+    self_cell!(
+        struct NonEmptyCell {
+            owner: String,
+
+            #[covariant]
+            dependent: NonEmpty,
+        }
+
+        impl {Clone(from)}
+    );
+
+    // Bypasses From, so cloning the cell panics.
+    let empty = NonEmptyCell::new(String::new(), |owner| NonEmpty(owner));
+
+    let mut target = NonEmptyCell::new("Bunt".into(), |owner| owner.into());
+    let result = catch_unwind(std::panic::AssertUnwindSafe(|| {
+        target.clone_from(&empty);
+    }));
+
+    assert!(result.is_err());
+    // The target is left untouched.
+    assert_eq!(target.borrow_owner(), "Bunt");
+    assert_eq!(target.borrow_dependent(), &NonEmpty("Bunt"));
+}
+
+#[test]
+fn clone_try_from_impl() {
+    use std::convert::TryFrom;
+
+    #[derive(Debug, PartialEq)]
+    struct Digits<'a>(&'a str);
+
+    impl<'a> TryFrom<&'a String> for Digits<'a> {
+        type Error = ();
+
+        fn try_from(owner: &'a String) -> Result<Self, ()> {
+            if owner.chars().all(|c| c.is_ascii_digit()) {
+                std::result::Result::Ok(Digits(owner))
+            } else {
+                std::result::Result::Err(())
+            }
+        }
+    }
+
+    self_cell!(
+        struct DigitsCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Digits,
+        }
+
+        impl {Clone(try_from)}
+    );
+
+    let cell = DigitsCell::try_new("1312".into(), |owner| Digits::try_from(owner)).unwrap();
+    let clone = cell.clone();
+    assert_eq!(clone.borrow_dependent(), &Digits("1312"));
+}
+
+#[test]
+fn clone_builder_impl() {
+    fn first_words<'a>(owner: &'a str) -> Ast<'a> {
+        Ast(owner.split(' ').take(2).collect())
+    }
+
+    self_cell!(
+        struct FirstWordsCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+
+        impl {Clone(builder = first_words)}
+    );
+
+    let cell = FirstWordsCell::new("Nebel ziehen auf".into(), |owner| first_words(owner));
+    let clone = cell.clone();
+    assert_eq!(clone.borrow_owner(), "Nebel ziehen auf");
+    assert_eq!(clone.borrow_dependent(), &Ast(vec!["Nebel", "ziehen"]));
+}
+
+#[test]
+fn display_impl() {
+    type FirstWord<'a> = &'a str;
+
+    self_cell!(
+        struct DependentDisplayCell {
+            owner: String,
+
+            #[covariant]
+            dependent: FirstWord,
+        }
+
+        impl {Display}
+    );
+
+    self_cell!(
+        struct OwnerDisplayCell {
+            owner: String,
+
+            #[covariant]
+            dependent: FirstWord,
+        }
+
+        impl {Display(owner)}
+    );
+
+    fn first_word(owner: &str) -> &str {
+        owner.split(' ').next().unwrap()
+    }
+
+    let cell = DependentDisplayCell::new("Kurz davor".into(), |owner| first_word(owner));
+    assert_eq!(format!("{:>6}", cell), "  Kurz");
+
+    let cell = OwnerDisplayCell::new("Kurz davor".into(), |owner| first_word(owner));
+    assert_eq!(cell.to_string(), "Kurz davor");
+}
+
+#[test]
+fn error_impl() {
+    use std::error::Error;
+    use std::fmt;
+
+    #[derive(Debug)]
+    struct Cause;
+
+    impl fmt::Display for Cause {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "cause")
+        }
+    }
+
+    impl Error for Cause {}
+
+    #[derive(Debug)]
+    struct ParseError<'a> {
+        token: &'a str,
+        cause: Cause,
+    }
+
+    impl<'a> fmt::Display for ParseError<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "unexpected token: {}", self.token)
+        }
+    }
+
+    impl<'a> Error for ParseError<'a> {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.cause)
+        }
+    }
+
+    self_cell!(
+        struct ParseErrorCell {
+            owner: String,
+
+            #[covariant]
+            dependent: ParseError,
+        }
+
+        impl {Debug, Display, Error}
+    );
+
+    fn parse(input: String) -> Result<usize, Box<dyn Error>> {
+        let cell = ParseErrorCell::new(input, |input| ParseError {
+            token: &input[4..7],
+            cause: Cause,
+        });
+        std::result::Result::Err(Box::new(cell))
+    }
+
+    let err = parse("let 42x = 3".into()).unwrap_err();
+    assert_eq!(err.to_string(), "unexpected token: 42x");
+    assert_eq!(err.source().unwrap().to_string(), "cause");
+}
+
+#[test]
+fn read_impl() {
+    use std::io::{BufRead, Cursor, Read};
+
+    type BytesCursor<'a> = Cursor<&'a [u8]>;
+
+    self_cell!(
+        struct BytesReader {
+            owner: Vec<u8>,
+
+            #[covariant]
+            dependent: BytesCursor,
+        }
+
+        impl {Read, BufRead}
+    );
+
+    fn read_all(mut reader: impl Read) -> String {
+        let mut out = String::new();
+        reader.read_to_string(&mut out).unwrap();
+        out
+    }
+
+    let reader = BytesReader::new(b"first\nsecond\nthird".to_vec(), |owner| {
+        Cursor::new(&owner[..])
+    });
+    assert_eq!(read_all(reader), "first\nsecond\nthird");
+
+    let mut reader = BytesReader::new(b"first\nsecond\nthird".to_vec(), |owner| {
+        Cursor::new(&owner[..])
+    });
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "first\n");
+    assert_eq!(reader.borrow_dependent().position(), 6);
+
+    let rest: Vec<String> = reader.lines().map(|line| line.unwrap()).collect();
+    assert_eq!(rest, ["second", "third"]);
+}
+
+#[test]
+fn write_impl() {
+    use std::io::{Cursor, Write};
+
+    type VecCursor<'a> = Cursor<&'a mut Vec<u8>>;
+
+    self_cell!(
+        struct VecWriter {
+            owner: MutBorrow<Vec<u8>>,
+
+            #[covariant]
+            dependent: VecCursor,
+        }
+
+        impl {Write}
+    );
+
+    let mut writer = VecWriter::new(MutBorrow::new(b"abc".to_vec()), |owner| {
+        let mut cursor = Cursor::new(owner.borrow_mut());
+        cursor.set_position(3);
+        cursor
+    });
+    write!(writer, "{}-{}", 1, 2).unwrap();
+    writer.flush().unwrap();
+
+    assert_eq!(writer.into_owner().into_inner(), b"abc1-2");
+}
+
 #[test]
 fn lazy_ast() {
     #[derive(Debug)]
@@ -906,3 +1674,193 @@ fn mut_borrow_new_borrow_mut() {
 
     cell.borrow_owner().borrow_mut();
 }
+
+#[test]
+fn dyn_cell() {
+    type Chars<'a> = Vec<char>;
+
+    self_cell!(
+        struct CharsCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Chars,
+        }
+    );
+
+    fn owner_len<C: DynCell<Owner = String>>(cell: &C) -> usize {
+        cell.borrow_owner().len()
+    }
+
+    let ast_cell = PackedAstCell::new("some string".into(), |owner| owner.into());
+    assert_eq!(owner_len(&ast_cell), 11);
+
+    let cells: Vec<Box<dyn DynCell<Owner = String>>> = vec![
+        Box::new(ast_cell),
+        Box::new(CharsCell::new("xyz".into(), |owner| owner.chars().collect())),
+    ];
+
+    let owners: Vec<&String> = cells.iter().map(|cell| cell.borrow_owner()).collect();
+    assert_eq!(owners, ["some string", "xyz"]);
+
+    self_cell!(
+        struct ChildCell<'a> {
+            owner: &'a String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+    );
+
+    let child_owner = String::from("child");
+    let child_cell = ChildCell::new(&child_owner, |owner| Ast::from(*owner));
+    assert_eq!(*DynCell::borrow_owner(&child_cell), &child_owner);
+}
+
+#[test]
+#[cfg(feature = "live_cells")]
+fn live_cells() {
+    self_cell!(
+        struct CountedCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+    );
+
+    assert_eq!(CountedCell::live_cells(), 0);
+
+    let cell_a = CountedCell::new("abcdef".into(), |owner| owner.into());
+    let cell_b = CountedCell::try_new("abcdef".into(), |owner| -> Result<_, ()> {
+        std::result::Result::Ok(owner.into())
+    })
+    .unwrap();
+    assert_eq!(CountedCell::live_cells(), 2);
+
+    let failed = CountedCell::try_new_or_recover("abcdef".into(), |_| std::result::Result::Err(()));
+    assert!(failed.is_err());
+    assert_eq!(CountedCell::live_cells(), 2);
+
+    drop(cell_a);
+    assert_eq!(CountedCell::live_cells(), 1);
+
+    std::mem::forget(CountedCell::new("leaked".into(), |owner| owner.into()));
+    assert_eq!(CountedCell::live_cells(), 2);
+
+    let _ = cell_b.into_owner();
+    assert_eq!(CountedCell::live_cells(), 1);
+}
+
+#[test]
+#[cfg(feature = "tracing")]
+fn tracing_spans() {
+    use std::sync::Mutex;
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Dispatch, Event, Metadata, Subscriber};
+
+    // The global default is shared with the other tests, spans of other cells
+    // are filtered out by name.
+    static SPANS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct SpanRecorder;
+
+    struct FieldsVisitor(String);
+
+    impl Visit for FieldsVisitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+
+    impl Subscriber for SpanRecorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let mut visitor = FieldsVisitor(attrs.metadata().name().to_string());
+            attrs.record(&mut visitor);
+
+            let mut spans = SPANS.lock().unwrap();
+            if visitor.0.contains("TracedCell") {
+                spans.push(visitor.0);
+            }
+            Id::from_u64(spans.len() as u64 + 1)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    self_cell!(
+        struct TracedCell {
+            owner: String,
+
+            #[covariant]
+            dependent: Ast,
+        }
+    );
+
+    tracing::dispatcher::set_global_default(Dispatch::new(SpanRecorder)).unwrap();
+
+    let mut cell = TracedCell::new("Der Wind".into(), |owner| owner.into());
+    cell.with_owner_mut(|owner| owner.push('!'), |owner| owner.into());
+    cell.replace_owner("Die Flut".into(), |owner| owner.into());
+    cell.map_dependent(|dependent| dependent);
+    cell.replace_dependent_with(|owner| owner.into(), |_old| ());
+
+    assert_eq!(
+        *SPANS.lock().unwrap(),
+        vec![
+            "self_cell::build cell=\"TracedCell\"",
+            "self_cell::rebuild cell=\"TracedCell\" method=\"with_owner_mut\"",
+            "self_cell::rebuild cell=\"TracedCell\" method=\"replace_owner\"",
+            "self_cell::rebuild cell=\"TracedCell\" method=\"map_dependent\"",
+            "self_cell::rebuild cell=\"TracedCell\" method=\"replace_dependent_with\"",
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "layout_dump")]
+fn layout_dump() {
+    type Index<'a> = (&'a u8, u8);
+
+    self_cell!(
+        struct IndexCell {
+            owner: u8,
+
+            #[covariant]
+            dependent: Index,
+        }
+    );
+
+    let cell = IndexCell::new(7, |owner| (owner, 3));
+    let layout = cell.layout_dump();
+
+    assert_eq!(layout.struct_name, "IndexCell");
+    assert_eq!(layout.size, std::mem::size_of::<(u8, (&u8, u8))>());
+    assert_eq!(layout.owner.name, "u8");
+    assert_eq!(layout.owner.size, 1);
+    assert_eq!(layout.dependent.name, "Index");
+    assert_eq!(layout.dependent.size, std::mem::size_of::<(&u8, u8)>());
+    assert_eq!(
+        layout.address,
+        cell.borrow_owner() as *const u8 as usize - layout.owner.offset
+    );
+
+    let dump = layout.to_string();
+    assert!(dump.starts_with("IndexCell at 0x"));
+    assert!(dump.contains(" owner      u8 (size 1, align 1)\n"));
+    assert!(dump.contains(" padding\n"));
+}